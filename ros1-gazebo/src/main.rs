@@ -1,9 +1,15 @@
 mod msg;
 mod pipeline;
 
+use std::path::PathBuf;
+
 use structopt::StructOpt;
 use tokio::{signal, sync::mpsc};
 use turbojpeg::{Compressor, Image, PixelFormat};
+use vkcv::{
+    description::PipelineDescription,
+    filtering::{KalmanConfig, PointFilter},
+};
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "ros1-publisher")]
@@ -30,7 +36,7 @@ struct Opt {
     #[structopt(short, long, default_value = "60")]
     compressor_quality: i32,
 
-    /// Lock timeout in ms.
+    /// Lock timeout in ms, the target position is predicted in between.
     #[structopt(short, long, default_value = "1000")]
     lock_timeout: u64,
 
@@ -38,6 +44,10 @@ struct Opt {
     #[structopt(short, long, default_value = "16")]
     min_area: u32,
 
+    /// Pipeline description (TOML or JSON), defaults to the color tracker in 'pipeline.toml'.
+    #[structopt(short, long, parse(from_os_str))]
+    pipeline: Option<PathBuf>,
+
     /// roslaunch adds some special args
     /// e.g. __name:=... __log:=...
     #[structopt(name = "__ros_args", default_value = "")]
@@ -76,6 +86,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         rosrust::publish::<pipeline::RosImageCompressed>("/vkcv/camera_depth_image/compressed", 1)?;
 
     let lock_pub = rosrust::publish::<pipeline::Bool>("/vkcv/lock", 1)?;
+    let confidence_pub = rosrust::publish::<pipeline::Float32>("/vkcv/lock_confidence", 1)?;
+    let filtered_point_pub = rosrust::publish::<pipeline::Point3>("/vkcv/filtered_point", 1)?;
 
     // setup vkcv
     let cv_config = pipeline::Config {
@@ -87,12 +99,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         ..Default::default()
     };
 
+    let description = match &opt.pipeline {
+        Some(path) => PipelineDescription::load(path)?,
+        None => PipelineDescription::from_toml(include_str!("../pipeline.toml"))?,
+    };
+
     // vkcv processing thread
     let vkcv_handle = tokio::task::spawn_blocking(move || {
         let ros_camera_info = ros_camera_info_rx.blocking_recv().unwrap();
 
         pipeline::process_blocking(
             cv_config,
+            description,
             cv_point3_tx,
             cv_image_tx,
             cv_depth_image_tx,
@@ -111,7 +129,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // publishing thread
     let main_handle = tokio::task::spawn(async move {
-        let mut last_seen = None;
+        // temporal filtering of the target position
+        let mut filter = PointFilter::new(KalmanConfig {
+            max_dropout: opt.lock_timeout as f32 / 1000.0,
+            ..Default::default()
+        });
+        let mut last_step = std::time::Instant::now();
 
         // subscribers
         // declare them here such that they go out of scope when exiting this task
@@ -153,18 +176,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         loop {
             tokio::select! {
                 _ = lock_ticker.tick() => {
-                    let has_lock = if let Some(last_seen) = last_seen {
-                        std::time::Instant::now() - last_seen < std::time::Duration::from_millis(opt.lock_timeout)
-                    } else {
-                        false
-                    };
+                    let now = std::time::Instant::now();
+                    filter.predict((now - last_step).as_secs_f32());
+                    last_step = now;
+
+                    let has_lock = filter.is_locked();
 
                     lock_pub.send(pipeline::Bool {
                         data: has_lock
                     }).expect("Failed to send '~/lock'");
+                    confidence_pub.send(pipeline::Float32 {
+                        data: filter.confidence()
+                    }).expect("Failed to send '~/lock_confidence'");
 
                     if opt.verbose {
-                        println!("Has lock: {}", has_lock);
+                        println!("Has lock: {} ({:.2})", has_lock, filter.confidence());
                     }
 
                     if !rosrust::is_ok() {
@@ -177,7 +203,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
                 Some(msg) = cv_point3_rx.recv() => {
-                    last_seen = Some(std::time::Instant::now());
+                    let now = std::time::Instant::now();
+                    filter.predict((now - last_step).as_secs_f32());
+                    last_step = now;
+
+                    // neither outliers nor unconfirmed tracks are published as filtered point
+                    if filter.update([msg.x as f32, msg.y as f32, msg.z as f32]) && filter.is_locked() {
+                        if let Some([x, y, z]) = filter.position() {
+                            filtered_point_pub.send(pipeline::Point3 {
+                                x: x as f64,
+                                y: y as f64,
+                                z: z as f64,
+                            }).expect("Failed to send '~/filtered_point'");
+                        }
+                    }
 
                     // TODO: publish point in vehicle frame
                     if let Ok(pose) = uav_pose.lock() {
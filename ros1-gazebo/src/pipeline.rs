@@ -2,34 +2,22 @@ use tokio::sync::mpsc::{Receiver, Sender};
 
 use crate::msg;
 use vkcv::{
+    description::{PipelineDescription, Registry},
     draw::{draw_centroid, OwnedImage},
     endpoints::{image_download::ImageDownload, image_upload::ImageUpload},
-    processing_elements::{
-        color_filter::ColorFilter,
-        hsvconv::Hsvconv,
-        input::Input,
-        morphology::{Morphology, Operation},
-        output::Output,
-        pooling::{self, Pooling},
-        tracker::{self, Canvas, PoolingStrategy, Tracker},
-    },
+    processing_elements::{input::Input, output::Output, tracker},
     utils::{cv_pipeline_sequential, ImageInfo},
-    vk_init,
-    vulkano::{
-        self,
-        sync::{self, GpuFuture},
-    },
+    vk_init, vulkano,
 };
 
 pub type Point3 = msg::geometry_msgs::Point;
 pub type Bool = msg::std_msgs::Bool;
+pub type Float32 = msg::std_msgs::Float32;
 pub type Image = OwnedImage;
 pub type RosImageCompressed = msg::sensor_msgs::CompressedImage;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
-    pub hsv_min: [f32; 3],
-    pub hsv_max: [f32; 3],
     pub min_area: u32,
     pub transmit_image: bool,
     pub transmit_depth_image: bool,
@@ -40,8 +28,6 @@ pub struct Config {
 impl Default for Config {
     fn default() -> Self {
         Self {
-            hsv_min: [0.2, 0.6, 0.239],
-            hsv_max: [0.4, 1.0, 1.0],
             min_area: 4 * 4,
             transmit_image: false,
             transmit_depth_image: false,
@@ -53,6 +39,7 @@ impl Default for Config {
 
 pub fn process_blocking(
     config: Config,
+    description: PipelineDescription,
     sender_point3: Sender<Point3>,
     sender_image: Sender<OwnedImage>,
     sender_depth_image: Sender<OwnedImage>,
@@ -63,7 +50,8 @@ pub fn process_blocking(
     println!("CV: Realsense camera tracker");
 
     // init device
-    let ctx = vk_init::init().unwrap();
+    let ctx = vk_init::init()?;
+    ctx.pipeline_cache.load_default();
 
     // get image info
     let img_info = ImageInfo {
@@ -82,32 +70,19 @@ pub fn process_blocking(
     // and projection matrix K
     let camera_proj = nalgebra::Matrix3x4::from_row_slice(&camera_info.P);
 
-    // create a color tracking pipeline
+    // create the tracking pipeline, the description is expected to end with a tracker
     let pe_input = Input::new(pipeline_info);
-    let pe_hsv = Hsvconv::new();
-    let pe_hsv_filter = ColorFilter::new(config.hsv_min, config.hsv_max);
-    let pe_erode = Morphology::new(Operation::Erode);
-    let pe_dilate = Morphology::new(Operation::Dilate);
-    let pe_tracker = Tracker::new(PoolingStrategy::Pooling4, Canvas::Pad);
-    let pe_pooling = Pooling::new(pooling::Operation::Max); // 2x2
+    let elements = description.create(&Registry::default())?;
     let pe_out = Output::new();
 
-    let (pipeline_cb, input_io, output_io) = cv_pipeline_sequential(
-        &ctx,
-        &pe_input,
-        &[
-            &pe_hsv,
-            &pe_hsv_filter,
-            &pe_erode,
-            &pe_dilate,
-            &pe_pooling,
-            &pe_tracker,
-        ],
-        &pe_out,
-    );
-
-    let upload = ImageUpload::from_io(input_io).unwrap();
-    let mut download = ImageDownload::from_io(output_io).unwrap();
+    let elements: Vec<_> = elements.iter().map(|pe| pe.as_ref()).collect();
+    let (recording, input_io, output_io) =
+        cv_pipeline_sequential(&ctx, &pe_input, &elements, &pe_out)?;
+    ctx.pipeline_cache.save_default();
+    println!("CV: GPU memory: {}", recording.memory());
+
+    let upload = ImageUpload::from_io(input_io)?;
+    let mut download = ImageDownload::from_io(output_io)?;
 
     println!("CV: Entering main loop");
     while rosrust::is_ok() {
@@ -117,20 +92,16 @@ pub fn process_blocking(
         let depth_frame = ros_depth_image.blocking_recv().unwrap();
 
         // upload image to GPU
-        upload.copy_input_data(&color_frame.1);
+        upload.copy_input_data(&color_frame.1)?;
 
         // process on GPU
-        let future = sync::now(ctx.device.clone())
-            .then_execute(ctx.queue.clone(), pipeline_cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
+        let future = recording.submit(&ctx)?;
 
         // wait till finished
-        future.wait(None).unwrap(); // spin-lock
+        future.wait(None)?; // spin-lock
 
         // print results
-        let (c, area) = tracker::centroid(&download.transfer());
+        let (c, area) = tracker::centroid(&download.transfer()?)?;
         let area_px = (area * color_frame.0.area() as f32) as u32;
 
         //println!("got frame {}, {}", area_px, config.min_area);
@@ -1,5 +1,6 @@
 rosrust::rosmsg_include!(
     std_msgs / Bool,
+    std_msgs / Float32,
     sensor_msgs / CompressedImage,
     geometry_msgs / Point
 );
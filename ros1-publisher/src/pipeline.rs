@@ -1,30 +1,21 @@
-use std::{error::Error, sync::Arc};
+use std::{collections::VecDeque, error::Error};
 
 use crate::msg;
 use vkcv::{
+    description::{PipelineDescription, Registry},
     draw::{draw_centroid, OwnedImage},
-    endpoints::{image_download::ImageDownload, image_upload::ImageUpload},
-    processing_elements::{
-        color_filter::ColorFilter,
-        hsvconv::Hsvconv,
-        input::Input,
-        morphology::{Morphology, Operation},
-        output::Output,
-        pooling::{self, Pooling},
-        tracker::{self, Canvas, PoolingStrategy, Tracker},
-    },
-    realsense::Realsense,
+    frame_pipeline::FramePipeline,
+    params::Param,
+    processing_elements::{input::Input, output::Output, roi::Window, tracker},
+    realsense::{ColorFrame, DepthFrame, Realsense},
     utils::{cv_pipeline_sequential, ImageInfo},
     vk_init::{self, VkContext},
-    vulkano::{
-        self,
-        command_buffer::PrimaryAutoCommandBuffer,
-        sync::{self, GpuFuture},
-    },
+    vulkano,
 };
 
 pub type Point3 = msg::geometry_msgs::Point;
 pub type Bool = msg::std_msgs::Bool;
+pub type Float32 = msg::std_msgs::Float32;
 pub type Image = OwnedImage;
 pub type RosImageCompressed = msg::sensor_msgs::CompressedImage;
 
@@ -32,25 +23,24 @@ use tokio::sync::mpsc::UnboundedSender;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
-    pub hsv_min: [f32; 3],
-    pub hsv_max: [f32; 3],
     pub min_area: u32,
     pub transmit_image: bool,
     pub transmit_depth_image: bool,
     pub process_image: bool,
     pub verbose: bool,
+    // frame k+1 is captured and submitted while frame k is still processed
+    pub frames_in_flight: usize,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            hsv_min: [0.3, 0.6, 0.239],
-            hsv_max: [0.5, 1.0, 1.0],
             min_area: 4 * 4,
             transmit_image: false,
             transmit_depth_image: false,
             process_image: true,
             verbose: false,
+            frames_in_flight: 2,
         }
     }
 }
@@ -62,15 +52,18 @@ pub struct Pipeline {
     sender_depth_image: UnboundedSender<OwnedImage>,
     camera: Realsense,
 
-    pipeline_cb: Arc<PrimaryAutoCommandBuffer>,
-    upload: ImageUpload,
-    download: ImageDownload,
+    frames: FramePipeline,
+    // frame number, camera frames and window of the frames in flight
+    pending: VecDeque<(u64, ColorFrame, DepthFrame, Window)>,
+    // the window of the element named "roi" and its extent
+    roi: Option<(Param<Window>, [f32; 2])>,
     ctx: VkContext,
 }
 
 impl Pipeline {
     pub fn new(
         config: Config,
+        description: &PipelineDescription,
         sender_point3: UnboundedSender<Point3>,
         sender_image: UnboundedSender<OwnedImage>,
         sender_depth_image: UnboundedSender<OwnedImage>,
@@ -97,43 +90,51 @@ impl Pipeline {
         let img_info = camera.fetch_image(false).0.image_info();
 
         // init device
-        let ctx = vk_init::init().unwrap();
+        let ctx = vk_init::init()?;
+        ctx.pipeline_cache.load_default();
 
-        // create a color tracking pipeline
+        // create the tracking pipeline, the description is expected to end with a tracker
         let pe_input = Input::new(img_info);
-        let pe_hsv = Hsvconv::new();
-        let pe_hsv_filter = ColorFilter::new(config.hsv_min, config.hsv_max);
-        let pe_erode = Morphology::new(Operation::Erode);
-        let pe_dilate = Morphology::new(Operation::Dilate);
-        let pe_tracker = Tracker::new(PoolingStrategy::Pooling4, Canvas::Pad);
-        let pe_pooling = Pooling::new(pooling::Operation::Max); // 2x2
+        let elements = description.create(&Registry::default())?;
         let pe_out = Output::new();
 
-        let (pipeline_cb, input_io, output_io) = cv_pipeline_sequential(
-            &ctx,
-            &pe_input,
-            &[
-                &pe_hsv,
-                &pe_hsv_filter,
-                &pe_erode,
-                &pe_dilate,
-                &pe_pooling,
-                &pe_tracker,
-            ],
-            &pe_out,
-        );
+        // the window follows the target if the description has an element named "roi"
+        let roi = match description.handle::<Param<Window>>(&elements, "roi")? {
+            Some(window) => {
+                let index = description
+                    .elements
+                    .iter()
+                    .position(|e| e.name.as_deref() == Some("roi"))
+                    .unwrap();
+                let roi_info = elements[index].output_info(Some(img_info)).unwrap();
+
+                // resampled 1:1 while following
+                let extent = [
+                    (roi_info.width as f32 / img_info.width as f32).min(1.0),
+                    (roi_info.height as f32 / img_info.height as f32).min(1.0),
+                ];
+                println!("CV: Following the target with a {:?} window", extent);
+
+                Some((window, extent))
+            }
+            None => None,
+        };
 
-        let upload = ImageUpload::from_io(input_io).unwrap();
-        let download = ImageDownload::from_io(output_io).unwrap();
+        let elements: Vec<_> = elements.iter().map(|pe| pe.as_ref()).collect();
+        let frames = FramePipeline::new(&ctx, config.frames_in_flight, |ctx| {
+            cv_pipeline_sequential(ctx, &pe_input, &elements, &pe_out)
+        })?;
+        ctx.pipeline_cache.save_default();
+        println!("CV: GPU memory: {}", frames.memory());
 
         Ok(Self {
             config,
             sender_point3,
             sender_image,
             sender_depth_image,
-            pipeline_cb,
-            upload,
-            download,
+            frames,
+            pending: VecDeque::new(),
+            roi,
             camera,
             ctx,
         })
@@ -143,23 +144,54 @@ impl Pipeline {
         // grab depth and color image from the realsense
         let (color_image, depth_image) = self.camera.fetch_image(true);
 
-        // upload image to GPU
-        self.upload.copy_input_data(color_image.data_slice());
+        // the window processed by this frame
+        let window = match &self.roi {
+            Some((param, _)) => param.get(),
+            None => Window::full(),
+        };
 
-        // process on GPU
-        let future = sync::now(self.ctx.device.clone())
-            .then_execute(self.ctx.queue.clone(), self.pipeline_cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
+        // get actual depth image with holes filled, kept until the result is back
+        // (an entry left by a frame which failed to be submitted is replaced)
+        let frame = self.frames.next_frame();
+        self.pending.retain(|(f, ..)| *f < frame);
+        self.pending
+            .push_back((frame, color_image, depth_image.get(), window));
+
+        // upload image to GPU and process, hands back the result of an earlier frame
+        let data = self.pending.back().unwrap().1.data_slice();
+        let result = match self.frames.push(&self.ctx, data)? {
+            Some(result) => (result.frame, tracker::centroid(&result.image)),
+            None => return Ok(()),
+        };
 
-        // wait till finished
-        future.await?;
+        // the camera frames and window of the result, also if it cannot be read
+        let (frame, centroid) = result;
+        self.pending.retain(|(f, ..)| *f >= frame);
+        let (_, color_image, depth_image, window) = self.pending.pop_front().unwrap();
+
+        let (c, area) = match centroid {
+            Ok(centroid) => centroid,
+            Err(err) => {
+                // search the full frame again
+                if let Some((param, _)) = &self.roi {
+                    param.set(Window::full())?;
+                }
+                return Err(err.into());
+            }
+        };
 
         // print results
-        let (c, area) = tracker::centroid(&self.download.transfer());
+        let area = area * window.extent[0] * window.extent[1];
         let area_px = (area * color_image.area() as f32) as u32;
 
+        // process the window around the target with the next frame, or search the
+        // full frame once it is lost
+        if let Some((param, extent)) = &self.roi {
+            let target = (area_px > self.config.min_area).then_some(c);
+            param.set(window.follow(target, *extent))?;
+        }
+        let c = window.to_frame(c);
+
         // owned image
         let mut owned_image = OwnedImage {
             buffer: color_image.data_slice().to_vec(),
@@ -170,9 +202,6 @@ impl Pipeline {
             },
         };
 
-        // get actual depth image with holes filled
-        let depth_image = depth_image.get();
-
         // get the depth only if our object is bigger than 225px² (15x15)
         if area_px > self.config.min_area {
             let pixel_coords = [
@@ -30,6 +30,10 @@ struct Opt {
     /// Lock timeout in ms.
     #[structopt(short, long, default_value = "1000")]
     lock_timeout: u64,
+
+    /// Number of frames processed concurrently, adds latency but increases the frame rate.
+    #[structopt(long, default_value = "2")]
+    frames_in_flight: usize,
 }
 
 #[tokio::main]
@@ -66,12 +70,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         transmit_image: opt.transmit_image,
         verbose: opt.verbose,
         min_area: opt.min_area,
+        frames_in_flight: opt.frames_in_flight,
         ..Default::default()
     };
 
     let _vkcv_handle = tokio::task::spawn_blocking(move || {
         // vkcv loop
-        pipeline::process_blocking(cv_config, cv_point3_tx, cv_image_tx);
+        if let Err(err) = pipeline::process_blocking(cv_config, cv_point3_tx, cv_image_tx) {
+            eprintln!("CV: {}", err);
+        }
     });
 
     // setup jpeg compressor
@@ -1,6 +1,8 @@
+use std::{collections::VecDeque, error::Error};
+
 use vkcv::{
     draw::{draw_centroid, OwnedImage},
-    endpoints::{image_download::ImageDownload, image_upload::ImageUpload},
+    frame_pipeline::FramePipeline,
     processing_elements::{
         color_filter::ColorFilter,
         hsvconv::Hsvconv,
@@ -12,11 +14,7 @@ use vkcv::{
     },
     realsense::Realsense,
     utils::{cv_pipeline_sequential, ImageInfo},
-    vk_init,
-    vulkano::{
-        self,
-        sync::{self, GpuFuture},
-    },
+    vk_init, vulkano,
 };
 
 pub type Point3 = r2r::geometry_msgs::msg::Point;
@@ -33,6 +31,8 @@ pub struct Config {
     pub min_area: u32,
     pub transmit_image: bool,
     pub verbose: bool,
+    // frame k+1 is captured and submitted while frame k is still processed
+    pub frames_in_flight: usize,
 }
 
 impl Default for Config {
@@ -43,6 +43,7 @@ impl Default for Config {
             min_area: 225,
             transmit_image: false,
             verbose: false,
+            frames_in_flight: 2,
         }
     }
 }
@@ -51,7 +52,7 @@ pub fn process_blocking(
     config: Config,
     sender_point3: UnboundedSender<Point3>,
     sender_image: UnboundedSender<OwnedImage>,
-) {
+) -> Result<(), Box<dyn Error + Sync + Send>> {
     println!("CV: Realsense camera tracker");
 
     // set the default display, otherwise we fallback to llvmpipe
@@ -64,7 +65,7 @@ pub fn process_blocking(
         "CV: Opening camera ({}x{}@{}fps)",
         resolution[0], resolution[1], target_fps
     );
-    let mut camera = Realsense::open(&resolution, target_fps, &resolution, target_fps).unwrap();
+    let mut camera = Realsense::open(&resolution, target_fps, &resolution, target_fps)?;
 
     // grab a couple of frames
     for _ in 0..5 {
@@ -74,7 +75,7 @@ pub fn process_blocking(
     let img_info = camera.fetch_image(false).0.image_info();
 
     // init device
-    let (device, queue) = vk_init::init();
+    let ctx = vk_init::init()?;
 
     // create a color tracking pipeline
     let pe_input = Input::new(img_info);
@@ -86,81 +87,56 @@ pub fn process_blocking(
     let pe_pooling = Pooling::new(pooling::Operation::Max); // 2x2
     let pe_out = Output::new();
 
-    let (pipeline_cb, input_io, output_io) = cv_pipeline_sequential(
-        device.clone(),
-        queue.clone(),
-        &pe_input,
-        &[
-            &pe_hsv,
-            &pe_hsv_filter,
-            &pe_erode,
-            &pe_dilate,
-            &pe_pooling,
-            &pe_tracker,
-        ],
-        &pe_out,
-    );
-
-    let upload = ImageUpload::from_io(input_io).unwrap();
-    let mut download = ImageDownload::from_io(output_io).unwrap();
-
-    let mut avg_pipeline_execution_duration = std::time::Duration::ZERO;
-
-    // train
-    for i in 0..30 {
-        // process on GPU
-        let future = sync::now(device.clone())
-            .then_execute(queue.clone(), pipeline_cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
-
-        let pipeline_committed = std::time::Instant::now();
-
-        // wait till finished
-        future.wait(None).unwrap();
-
-        if i >= 10 {
-            if avg_pipeline_execution_duration.is_zero() {
-                avg_pipeline_execution_duration = std::time::Instant::now() - pipeline_committed;
-            }
-
-            avg_pipeline_execution_duration = std::time::Duration::from_secs_f32(
-                avg_pipeline_execution_duration.as_secs_f32() * 0.9
-                    + 0.1 * (std::time::Instant::now() - pipeline_committed).as_secs_f32(),
-            );
-        }
-    }
-
-    println!(
-        "CV: Average duration: {} ms/frame",
-        avg_pipeline_execution_duration.as_millis()
-    );
+    let mut frames = FramePipeline::new(&ctx, config.frames_in_flight, |ctx| {
+        cv_pipeline_sequential(
+            ctx,
+            &pe_input,
+            &[
+                &pe_hsv,
+                &pe_hsv_filter,
+                &pe_erode,
+                &pe_dilate,
+                &pe_pooling,
+                &pe_tracker,
+            ],
+            &pe_out,
+        )
+    })?;
+    println!("CV: GPU memory: {}", frames.memory());
+
+    // camera frames of the frames in flight
+    let mut pending = VecDeque::new();
 
     println!("CV: Entering main loop");
     loop {
         // grab depth and color image from the realsense
         let (color_image, depth_image) = camera.fetch_image(true);
 
-        // upload image to GPU
-        upload.copy_input_data(color_image.data_slice());
-
-        // process on GPU
-        let future = sync::now(device.clone())
-            .then_execute(queue.clone(), pipeline_cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
-
-        // wait till finished
-        std::thread::sleep(avg_pipeline_execution_duration); // the results are likely ready after we wake up
-        future.wait(None).unwrap(); // spin-lock?
+        // upload image to GPU and process, hands back the result of an earlier frame
+        let result = match frames.push(&ctx, color_image.data_slice())? {
+            Some(result) => {
+                if config.verbose {
+                    println!(
+                        "CV: frame {} took {} ms",
+                        result.frame,
+                        (result.completed - result.submitted).as_millis()
+                    );
+                }
+                Some(tracker::centroid(&result.image)?)
+            }
+            None => None,
+        };
 
         // get actual depth image with holes filled
-        let depth_image = depth_image.get();
+        pending.push_back((color_image, depth_image.get()));
+
+        let (c, area) = match result {
+            Some(result) => result,
+            None => continue,
+        };
+        let (color_image, depth_image) = pending.pop_front().unwrap();
 
         // print results
-        let (c, area) = tracker::centroid(&download.transfer());
         let area_px = (area * color_image.area() as f32) as u32;
 
         // owned image
@@ -189,25 +165,23 @@ pub fn process_blocking(
             }
 
             // draw centroid
-            draw_centroid(&mut owned_image, &pixel_coords);
+            draw_centroid(&mut owned_image, &pixel_coords, 2.0);
 
             // de-project to obtain a 3D point in camera coordinates
             if let Some(depth) = depth {
                 let point = camera.deproject_pixel(&pixel_coords, depth, &color_image);
 
-                sender_point3
-                    .send(Point3 {
-                        x: point[0] as f64,
-                        y: point[1] as f64,
-                        z: point[2] as f64,
-                    })
-                    .unwrap();
+                sender_point3.send(Point3 {
+                    x: point[0] as f64,
+                    y: point[1] as f64,
+                    z: point[2] as f64,
+                })?;
             }
         }
 
         // send image
         if config.transmit_image {
-            sender_image.send(owned_image).unwrap();
+            sender_image.send(owned_image)?;
         }
     }
 }
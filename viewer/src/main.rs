@@ -102,6 +102,23 @@ impl eframe::App for MyApp {
                 None => ui.heading("Nothing detected"),
             };
 
+            ui.collapsing("GPU timings", |ui| {
+                egui::Grid::new("grid_timings")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for stage in &res.timings.stages {
+                            ui.label(&stage.label);
+                            ui.label(format!("{:.1} μs", stage.micros));
+                            if let Some(info) = stage.image {
+                                ui.label(format!("{}x{}", info.width, info.height));
+                            }
+                            ui.end_row();
+                        }
+                        ui.label("total");
+                        ui.label(format!("{:.1} μs", res.timings.total_micros()));
+                    });
+            });
+
             ui.separator();
 
             egui::ScrollArea::new([false, true]).show(ui, |ui| {
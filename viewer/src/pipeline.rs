@@ -1,26 +1,22 @@
-use std::sync::Arc;
-
 use eframe::epaint::Hsva;
 use vkcv::{
+    description::{self, PipelineDescription, Registry},
     draw::{draw_centroid, OwnedImage},
     endpoints::{image_download::ImageDownload, image_upload::ImageUpload},
+    params::Param,
     processing_elements::{
-        color_filter::ColorFilter,
-        hsvconv::Hsvconv,
+        color_filter::{self, ColorFilter},
+        graph::PipelineGraph,
         input::Input,
-        morphology::{Morphology, Operation},
         output::Output,
-        pooling::{self, Pooling},
-        tracker::{self, Canvas, PoolingStrategy, Tracker},
+        tracker, Recording,
     },
+    profiler::{Profiler, TimingReport},
     realsense::Realsense,
-    utils::{cv_pipeline_sequential_with_taps, ImageInfo},
+    utils::ImageInfo,
     vk_init::{self, VkContext},
-    vulkano::command_buffer::PrimaryAutoCommandBuffer,
 };
 
-use vkcv::vulkano::sync::{self, GpuFuture};
-
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
     pub hsv_min: Hsva,
@@ -43,8 +39,9 @@ pub struct Pipeline {
     pub download: Vec<ImageDownload>,
     ctx: VkContext,
     cam: Realsense,
-    cb: Arc<PrimaryAutoCommandBuffer>,
-    img_info: ImageInfo,
+    recording: Recording,
+    hsv_range: Param<Vec<color_filter::Range>>,
+    profiler: Profiler,
 }
 
 impl Pipeline {
@@ -62,33 +59,59 @@ impl Pipeline {
 
         // init device
         let ctx = vk_init::init().unwrap();
+        ctx.pipeline_cache.load_default();
+
+        // an optional pipeline description can be passed as the first argument
+        let description = match std::env::args().nth(1) {
+            Some(path) => PipelineDescription::load(path).unwrap(),
+            None => PipelineDescription::from_toml(description::COLOR_TRACKER).unwrap(),
+        };
+
+        // the color filter shares its range with the ui
+        let cfg = Config::default();
+        let hsv_range = Param::new(vec![color_filter::Range::new(
+            [cfg.hsv_min.h, cfg.hsv_min.s, cfg.hsv_min.v],
+            [cfg.hsv_max.h, cfg.hsv_max.s, cfg.hsv_max.v],
+        )]);
+        let mut registry = Registry::default();
+        {
+            let hsv_range = hsv_range.clone();
+            registry.register("color_filter", move |range: color_filter::Range| {
+                hsv_range.set(vec![range])?;
+                Ok(Box::new(ColorFilter::with_params(hsv_range.clone())))
+            });
+        }
 
-        // create a color tracking pipeline
+        // create the tracking pipeline, the description is expected to end with a tracker
         let pe_input = Input::new(img_info);
-        let pe_hsv = Hsvconv::new();
-        let pe_hsv_filter = ColorFilter::new([0.20, 0.4, 0.239], [0.429, 1.0, 1.0]);
-        let pe_erode = Morphology::new(Operation::Erode);
-        let pe_dilate = Morphology::new(Operation::Dilate);
-        let pe_pooling = Pooling::new(pooling::Operation::Max); // 2x2
-        let pe_tracker = Tracker::new(PoolingStrategy::Pooling4, Canvas::Pad);
-
-        let (pipeline_cb, input_io, output_io) = cv_pipeline_sequential_with_taps::<_, Output>(
-            &ctx,
-            &pe_input,
-            &[
-                &pe_hsv,
-                &pe_hsv_filter,
-                &pe_erode,
-                &pe_dilate,
-                &pe_pooling,
-                &pe_tracker,
-            ],
-        );
-
-        let upload = ImageUpload::from_io(input_io).unwrap();
-        let download = output_io
+        let elements = description.create(&registry).unwrap();
+        let taps: Vec<_> = elements.iter().map(|_| Output::new()).collect();
+
+        // tap the output of every stage, timestamps are written around each element
+        let mut graph = PipelineGraph::new();
+        graph.reuse_images(true);
+        graph.profile(true);
+        let input_node = graph.add_source("input", &pe_input);
+        let mut last = input_node;
+        let mut tap_nodes = vec![];
+        for (i, pe) in elements.iter().enumerate() {
+            let node = graph.add(&format!("stage_{}", i), pe.as_ref());
+            graph.connect(last, node);
+
+            let tap = graph.add(&format!("tap_{}", i), &taps[i]);
+            graph.connect(node, tap);
+            tap_nodes.push(tap);
+
+            last = node;
+        }
+
+        let pipeline = graph.build(&ctx).unwrap();
+        ctx.pipeline_cache.save_default();
+
+        let upload = ImageUpload::from_io(pipeline.io(input_node).clone()).unwrap();
+        let download = tap_nodes
             .iter()
-            .map(|io| ImageDownload::from_io(io.clone()).unwrap())
+            .map(|tap| ImageDownload::from_io(pipeline.io(*tap).clone()).unwrap())
             .collect();
 
         Pipeline {
@@ -96,43 +119,20 @@ impl Pipeline {
             download,
             ctx,
             cam: camera,
-            cb: pipeline_cb,
-            img_info,
+            recording: pipeline.recording.clone(),
+            hsv_range,
+            profiler: pipeline.profiler.unwrap(),
         }
     }
 
     pub fn reconfigure(&mut self, cfg: &Config) {
-        // create a color tracking pipeline
-        let pe_input = Input::new(self.img_info);
-        let pe_hsv = Hsvconv::new();
-        let pe_hsv_filter = ColorFilter::new(
-            [cfg.hsv_min.h, cfg.hsv_min.s, cfg.hsv_min.v],
-            [cfg.hsv_max.h, cfg.hsv_max.s, cfg.hsv_max.v],
-        );
-        let pe_erode = Morphology::new(Operation::Erode);
-        let pe_dilate = Morphology::new(Operation::Dilate);
-        let pe_pooling = Pooling::new(pooling::Operation::Max); // 2x2
-        let pe_tracker = Tracker::new(PoolingStrategy::Pooling4, Canvas::Pad);
-
-        let (pipeline_cb, input_io, output_io) = cv_pipeline_sequential_with_taps::<_, Output>(
-            &self.ctx,
-            &pe_input,
-            &[
-                &pe_hsv,
-                &pe_hsv_filter,
-                &pe_erode,
-                &pe_dilate,
-                &pe_pooling,
-                &pe_tracker,
-            ],
-        );
-        self.cb = pipeline_cb;
-
-        self.upload = ImageUpload::from_io(input_io).unwrap();
-        self.download = output_io
-            .iter()
-            .map(|io| ImageDownload::from_io(io.clone()).unwrap())
-            .collect();
+        // takes effect with the next frame
+        self.hsv_range
+            .set(vec![color_filter::Range::new(
+                [cfg.hsv_min.h, cfg.hsv_min.s, cfg.hsv_min.v],
+                [cfg.hsv_max.h, cfg.hsv_max.s, cfg.hsv_max.v],
+            )])
+            .unwrap();
     }
 }
 
@@ -141,6 +141,7 @@ pub struct PipelineResult {
     pub target_pos: Option<[f32; 3]>,
     pub area: u32,
     pub dt: std::time::Duration,
+    pub timings: TimingReport,
 }
 
 impl Pipeline {
@@ -150,30 +151,36 @@ impl Pipeline {
         let t0 = std::time::Instant::now();
 
         // upload image to GPU
-        self.upload.copy_input_data(color_image.data_slice());
+        self.upload
+            .copy_input_data(color_image.data_slice())
+            .unwrap();
 
         // process on GPU
-        let future = sync::now(self.ctx.device.clone())
-            .then_execute(self.ctx.queue.clone(), self.cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
+        let future = self.recording.submit(&self.ctx).unwrap();
 
         // wait till finished
         future.wait(None).unwrap(); // spin-lock?
         let dt = std::time::Instant::now().duration_since(t0);
+        let timings = self.profiler.read().unwrap();
 
         // get processed depth image
         let depth_image = depth_image.get();
 
         // transfer all images to host
         for dl in &mut self.download {
-            dl.transfer();
+            dl.transfer().unwrap();
         }
 
         // print results
         let tf_image = self.download.last_mut().unwrap().transferred_image();
-        let (c, area) = tracker::centroid(&tf_image);
+        // an undecodable result is shown as no target
+        let (c, area) = match tracker::centroid(&tf_image) {
+            Ok(result) => result,
+            Err(err) => {
+                eprintln!("failed to read the tracker output: {}", err);
+                ([0.0, 0.0], 0.0)
+            }
+        };
         let area_px = (area * color_image.area() as f32) as u32;
 
         let mut owned_image = OwnedImage {
@@ -210,6 +217,7 @@ impl Pipeline {
             target_pos: point,
             area: area_px,
             dt,
+            timings,
         }
     }
 }
@@ -1,17 +1,12 @@
-use std::io::Write;
+use std::{collections::VecDeque, io::Write};
 
 use vkcv::{
+    description::{self, PipelineDescription, Registry},
     draw::{draw_centroid, OwnedImage},
-    endpoints::{image_download::ImageDownload, image_upload::ImageUpload},
-    processing_elements::{
-        color_filter::ColorFilter,
-        hsvconv::Hsvconv,
-        input::Input,
-        morphology::{Morphology, Operation},
-        output::Output,
-        pooling::{self, Pooling},
-        tracker::{self, Canvas, PoolingStrategy, Tracker},
-    },
+    endpoints::image_upload::ImageUpload,
+    frame_pipeline::FramePipeline,
+    processing_elements::{input::Input, output::Output, tracker},
+    profiler,
     realsense::Realsense,
     utils::{self, ImageInfo},
     utils::{cv_pipeline_sequential, cv_pipeline_sequential_debug},
@@ -19,7 +14,6 @@ use vkcv::{
 };
 
 use anyhow::Result;
-use vulkano::sync::{self, GpuFuture};
 
 use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
 
@@ -40,6 +34,12 @@ fn main() -> Result<()> {
         .write_all(&"frame,pipeline_time,fps,cpu\n".as_bytes())
         .unwrap();
 
+    // per stage GPU timings, written if DBG_PROFILE is set
+    let stages_file =
+        std::fs::File::create(format!("{}/stages.csv", env!("CARGO_MANIFEST_DIR"))).unwrap();
+    let mut stages_buf = std::io::BufWriter::new(stages_file);
+    writeln!(stages_buf, "{}", profiler::CSV_HEADER).unwrap();
+
     // v3d specs/properties:
     //
     // maxComputeWorkGroupSize: 256
@@ -75,114 +75,60 @@ fn main() -> Result<()> {
     let img_info = camera.fetch_image(false).0.image_info();
 
     // init device
-    let ctx = vk_init::init().unwrap();
+    let ctx = vk_init::init()?;
+    ctx.pipeline_cache.load_default();
+
+    // an optional pipeline description can be passed as the first argument
+    let description = match std::env::args().nth(1) {
+        Some(path) => PipelineDescription::load(path)?,
+        None => PipelineDescription::from_toml(description::COLOR_TRACKER)?,
+    };
 
-    // create a color tracking pipeline
+    // create the tracking pipeline, the description is expected to end with a tracker
     let pe_input = Input::new(img_info);
-    let pe_hsv = Hsvconv::new();
-    let pe_hsv_filter = ColorFilter::new([0.20, 0.4, 0.239], [0.429, 1.0, 1.0]);
-    let pe_erode = Morphology::new(Operation::Erode);
-    let pe_dilate = Morphology::new(Operation::Dilate);
-    let pe_pooling = Pooling::new(pooling::Operation::Max); // 2x2
-    let pe_tracker = Tracker::new(PoolingStrategy::Pooling4, Canvas::Pad);
+    let elements = description.create(&Registry::default())?;
+    let elements: Vec<_> = elements.iter().map(|pe| pe.as_ref()).collect();
     let pe_out = Output::new();
 
-    let (pipeline_cb, input_io, output_io) = cv_pipeline_sequential(
-        &ctx,
-        &pe_input,
-        &[
-            &pe_hsv,
-            &pe_hsv_filter,
-            &pe_erode,
-            &pe_dilate,
-            &pe_pooling,
-            &pe_tracker,
-        ],
-        &pe_out,
-    );
-
-    let pipeline_dbg = cv_pipeline_sequential_debug(
-        &ctx,
-        &pe_input,
-        &[
-            &pe_hsv,
-            &pe_hsv_filter,
-            &pe_erode,
-            &pe_dilate,
-            &pe_pooling,
-            &pe_tracker,
-        ],
-        &pe_out,
-    );
-
-    let upload = ImageUpload::from_io(input_io).unwrap();
-    let mut download = ImageDownload::from_io(output_io).unwrap();
-
-    let mut avg_pipeline_execution_duration = std::time::Duration::ZERO;
-
-    // train
-    for i in 0..30 {
-        // process on GPU
-        let future = sync::now(ctx.device.clone())
-            .then_execute(ctx.queue.clone(), pipeline_cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
-
-        let pipeline_committed = std::time::Instant::now();
-
-        // wait till finished
-        future.wait(None).unwrap();
+    // frame k+1 is uploaded and submitted while frame k is still processed
+    let mut frame_pipeline = FramePipeline::new(&ctx, 2, |ctx| {
+        cv_pipeline_sequential(ctx, &pe_input, &elements, &pe_out)
+    })?;
 
-        if i >= 10 {
-            if avg_pipeline_execution_duration.is_zero() {
-                avg_pipeline_execution_duration = std::time::Instant::now() - pipeline_committed;
-            }
-
-            avg_pipeline_execution_duration = std::time::Duration::from_secs_f32(
-                avg_pipeline_execution_duration.as_secs_f32() * 0.9
-                    + 0.1 * (std::time::Instant::now() - pipeline_committed).as_secs_f32(),
-            );
-        }
-    }
-
-    println!(
-        "Average duration: {}",
-        avg_pipeline_execution_duration.as_millis()
-    );
+    // separate elements, the debug pipeline runs concurrently to the frames in
+    // flight and must not share their state
+    let elements_dbg = description.create(&Registry::default())?;
+    let elements_dbg: Vec<_> = elements_dbg.iter().map(|pe| pe.as_ref()).collect();
+    let pipeline_dbg = cv_pipeline_sequential_debug(&ctx, &pe_input, &elements_dbg, &pe_out)?;
+    ctx.pipeline_cache.save_default();
+    println!("{} compute pipelines", ctx.pipeline_cache.len());
 
     let start_of_program = std::time::Instant::now();
     let mut frame = 0u32;
     let mut last_frame = 0;
 
+    // camera frames of the frames in flight
+    let mut pending = VecDeque::new();
+
     loop {
         // grab depth and color image from the realsense
         let (color_image, depth_image) = camera.fetch_image(true);
 
-        // time
-        let pipeline_started = std::time::Instant::now();
-
-        // upload image to GPU
-        upload.copy_input_data(color_image.data_slice());
-
-        // process on GPU
-        let future = sync::now(ctx.device.clone())
-            .then_execute(ctx.queue.clone(), pipeline_cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
-
-        // wait till finished
-        std::thread::sleep(avg_pipeline_execution_duration); // the results are likely ready after we wake up
-        future.wait(None).unwrap(); // spin-lock?
+        // upload image to GPU and process, hands back the result of an earlier frame
+        let result = frame_pipeline.push(&ctx, color_image.data_slice())?;
 
         // get processed depth image
-        let depth_image = depth_image.get();
+        pending.push_back((color_image, depth_image.get()));
+
+        let result = match result {
+            Some(result) => result,
+            None => continue,
+        };
+        let (color_image, depth_image) = pending.pop_front().unwrap();
 
         // print results
-        let pipeline_dt = std::time::Instant::now() - pipeline_started;
-        let tf_image = download.transfer();
-        let (c, area) = tracker::centroid(&tf_image);
+        let pipeline_dt = result.completed - result.submitted;
+        let (c, area) = tracker::centroid(&result.image)?;
         let area_px = (area * color_image.area() as f32) as u32;
 
         if DBG_PROFILE {
@@ -229,21 +175,23 @@ fn main() -> Result<()> {
                     &format!("out/centroid-{}", frame),
                     &owned_image.buffer,
                     &img_info,
-                );
+                )?;
             }
         }
 
         // debug
         // break down the cost of the individual stages
         if DBG_PROFILE && frame % 30 == 0 {
+            let upload = ImageUpload::from_io(pipeline_dbg.input.clone())?;
+            upload.copy_input_data(color_image.data_slice())?;
+
             // time the execution of the individual stages
-            pipeline_dbg.time(&ctx);
+            let timings = pipeline_dbg.time(&ctx)?;
+            timings.write_csv(&mut stages_buf, frame as u64).unwrap();
 
             // save a snapshot of all stages in the pipeline
-            let upload = ImageUpload::from_io(pipeline_dbg.input.clone()).unwrap();
-            upload.copy_input_data(color_image.data_slice());
             let prefix = std::time::Instant::now().duration_since(start_of_program);
-            pipeline_dbg.save_all(&ctx, "out", &format!("{}-", prefix.as_millis()));
+            pipeline_dbg.save_all(&ctx, "out", &format!("{}-", prefix.as_millis()))?;
         }
 
         // print stats
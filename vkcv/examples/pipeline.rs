@@ -32,10 +32,10 @@ fn main() -> Result<()> {
     std::env::set_var("DISPLAY", ":0");
     std::env::set_var("V3D_DEBUG", "perf");
 
-    let (img_info, img_data) = load_image("lab_image_2_rgba.png");
+    let (img_info, img_data) = load_image("lab_image_2_rgba.png")?;
 
     // init device
-    let ctx = vk_init::init().unwrap();
+    let ctx = vk_init::init()?;
 
     // create a convolution pipeline
     let pe_input = Input::new(img_info);
@@ -63,30 +63,32 @@ fn main() -> Result<()> {
             &pe_tracker,
         ],
         &pe_out,
-    );
+    )?;
 
-    let upload = ImageUpload::from_io(dp.input.clone()).unwrap();
-    let mut download = ImageDownload::from_io(dp.output.clone()).unwrap();
+    println!("Pipeline memory: {}", dp.memory);
+
+    let upload = ImageUpload::from_io(dp.input.clone())?;
+    let mut download = ImageDownload::from_io(dp.output.clone())?;
 
     // let color_image = realsense.fetch_image();
     //println!("{} x {}", color_image.width(), color_image.height());
     let pipeline_started = std::time::Instant::now();
 
     // upload image to GPU
-    upload.copy_input_data(&img_data);
+    upload.copy_input_data(&img_data)?;
 
     // process on GPU & wait till finished
-    dp.time(&ctx);
+    dp.time(&ctx)?;
 
     // save images
-    dp.save_all(&ctx, "out", "pipeline-");
+    dp.save_all(&ctx, "out", "pipeline-")?;
 
     let pipeline_dt = std::time::Instant::now() - pipeline_started;
     println!("Pipeline took {} us", pipeline_dt.as_micros());
 
-    let tf_img = download.transfer();
-    tf_img.save_output_buffer("out_0.png");
-    dbg!(tracker::centroid(&tf_img));
+    let tf_img = download.transfer()?;
+    tf_img.save_output_buffer("out_0.png")?;
+    dbg!(tracker::centroid(&tf_img)?);
 
     Ok(())
 }
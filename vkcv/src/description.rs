@@ -0,0 +1,553 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::VkcvError,
+    params::Parameters,
+    processing_elements::{
+        background_subtractor::{self, BackgroundSubtractor},
+        binary_op::Not,
+        canny::{self, Canny},
+        color_classifier::{Class, ColorClassifier},
+        color_filter::{ColorFilter, Range},
+        connected_components::{ComponentStats, ConnectedComponents, Connectivity},
+        convolution::{Convolution, Kernel},
+        convolution_2p::Convolution2Pass,
+        frame_difference::{self, FrameDifference},
+        gaussian_blur::{Blur, GaussianBlur},
+        grayscale::Grayscale,
+        hsvconv::Hsvconv,
+        label_tracker::LabelTracker,
+        morphology::{self, Morphology},
+        morphology_ex::{self, MorphologyEx},
+        pooling::{self, Pooling},
+        roi::{Roi, Window},
+        sobel::Sobel,
+        tracker::{Canvas, PoolingStrategy, Tracker},
+        ProcessingElement,
+    },
+};
+
+// hsv conversion, color filter, close, open, pooling and tracker
+pub const COLOR_TRACKER: &str = include_str!("../pipelines/color_tracker.toml");
+
+// the color tracker on a window of the frame, the element named "roi" is
+// expected to follow the target
+pub const COLOR_TRACKER_ROI: &str = include_str!("../pipelines/color_tracker_roi.toml");
+
+// A linear chain of processing elements as read from a TOML or JSON file, e.g.
+//
+//     [[elements]]
+//     type = "morphology"
+//     op = "erode"
+//
+// The input and output elements are not part of the description, these
+// depend on the camera resp. on how the result is consumed. Elements can be
+// given a unique name to look up their parameters after creation, see
+// `PipelineDescription::handle`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PipelineDescription {
+    pub elements: Vec<ElementDescription>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ElementDescription {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // passed to the constructor registered for `kind`
+    #[serde(flatten)]
+    pub params: Map<String, Value>,
+}
+
+impl PipelineDescription {
+    pub fn from_toml(s: &str) -> Result<Self, VkcvError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, VkcvError> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    // files ending in ".json" are read as JSON, everything else as TOML
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, VkcvError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|e| {
+            VkcvError::Description(format!("failed to read '{}': {}", path.display(), e))
+        })?;
+
+        match path.extension() {
+            Some(ext) if ext == "json" => Self::from_json(&s),
+            _ => Self::from_toml(&s),
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String, VkcvError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    // creates the elements in the order of the description, ready to be
+    // passed to e.g. `cv_pipeline_sequential`
+    pub fn create(
+        &self,
+        registry: &Registry,
+    ) -> Result<Vec<Box<dyn ProcessingElement>>, VkcvError> {
+        let mut names = HashSet::new();
+        for name in self.elements.iter().filter_map(|e| e.name.as_ref()) {
+            if !names.insert(name) {
+                return Err(VkcvError::Description(format!(
+                    "element name '{}' is not unique",
+                    name
+                )));
+            }
+        }
+
+        self.elements
+            .iter()
+            .enumerate()
+            .map(|(i, element)| {
+                registry
+                    .create(element)
+                    .map_err(|e| e.in_element(&format!("element {} ({})", i, element.kind)))
+            })
+            .collect()
+    }
+
+    // the parameters of the element named `name`, `elements` as returned by
+    // `create`, e.g.
+    //
+    //     let window: Option<Param<Window>> = description.handle(&elements, "roi")?;
+    //
+    // none if there is no such element
+    pub fn handle<H: 'static>(
+        &self,
+        elements: &[Box<dyn ProcessingElement>],
+        name: &str,
+    ) -> Result<Option<H>, VkcvError> {
+        let index = match self
+            .elements
+            .iter()
+            .position(|e| e.name.as_deref() == Some(name))
+        {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let element = elements.get(index).ok_or_else(|| {
+            VkcvError::Description(format!("element '{}' has not been created", name))
+        })?;
+
+        match element.handle().map(|h| h.downcast::<H>()) {
+            Some(Ok(handle)) => Ok(Some(*handle)),
+            _ => Err(VkcvError::Description(format!(
+                "element '{}' ({}) has no parameters of type {}",
+                name,
+                element.name(),
+                std::any::type_name::<H>()
+            ))),
+        }
+    }
+}
+
+type Constructor = Box<dyn Fn(Value) -> Result<Box<dyn ProcessingElement>, VkcvError>>;
+
+// Maps the element types of a description to their constructors.
+//
+// The default registry knows all elements of this crate, applications can
+// register their own elements or replace the built-in constructors.
+pub struct Registry {
+    constructors: HashMap<String, Constructor>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoParams {}
+
+// either a single range or e.g.
+//
+//     ranges = [
+//         { rgb_min = [0.9, 0.5, 0.5], rgb_max = [0.1, 1.0, 1.0] },
+//         { rgb_min = [0.3, 0.5, 0.5], rgb_max = [0.4, 1.0, 1.0] },
+//     ]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorFilterParams {
+    Single(Range),
+    Multiple { ranges: Vec<Range> },
+}
+
+// unset settings default to those of the model
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackgroundSubtractorParams {
+    #[serde(default)]
+    model: background_subtractor::Model,
+    learning_rate: Option<f32>,
+    threshold: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColorClassifierParams {
+    classes: Vec<Class>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConnectedComponentsParams {
+    #[serde(default)]
+    connectivity: Connectivity,
+    #[serde(default)]
+    min_area: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LabelTrackerParams {
+    classes: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MorphologyParams {
+    op: morphology::Operation,
+    #[serde(default)]
+    element: morphology::StructuringElement,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MorphologyExParams {
+    op: morphology_ex::Operation,
+    #[serde(default)]
+    element: morphology::StructuringElement,
+    #[serde(default = "one")]
+    iterations: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoolingParams {
+    op: pooling::Operation,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CannyParams {
+    sigma: f32,
+    low: f32,
+    high: f32,
+    #[serde(default)]
+    iterations: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RoiParams {
+    width: u32,
+    height: u32,
+    // initial window, updated through Roi::params
+    #[serde(default)]
+    window: Window,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrackerParams {
+    pooling: PoolingStrategy,
+    canvas: Canvas,
+    #[serde(default)]
+    extent: bool,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register("hsvconv", |_: NoParams| Ok(Box::new(Hsvconv::new())));
+        registry.register("grayscale", |_: NoParams| Ok(Box::new(Grayscale::new())));
+        registry.register("color_filter", |p: ColorFilterParams| {
+            Ok(Box::new(match p {
+                ColorFilterParams::Single(range) => ColorFilter::with_ranges(vec![range]),
+                ColorFilterParams::Multiple { ranges } => ColorFilter::with_ranges(ranges),
+            }))
+        });
+        registry.register("color_classifier", |p: ColorClassifierParams| {
+            Ok(Box::new(ColorClassifier::new(p.classes)))
+        });
+        // components smaller than min_area are removed, such that the noise does
+        // not take up the MAX_COMPONENTS rows of the component_stats table
+        registry.register("connected_components", |p: ConnectedComponentsParams| {
+            Ok(Box::new(ConnectedComponents::with_min_area(
+                p.connectivity,
+                p.min_area,
+            )))
+        });
+        registry.register("component_stats", |_: NoParams| {
+            Ok(Box::new(ComponentStats::new()))
+        });
+        registry.register("morphology", |p: MorphologyParams| {
+            p.element.validate()?;
+            Ok(Box::new(Morphology::with_element(p.op, p.element)))
+        });
+        registry.register("morphology_ex", |p: MorphologyExParams| {
+            p.element.validate()?;
+            Ok(Box::new(MorphologyEx::with_element(
+                p.op,
+                p.element,
+                p.iterations,
+            )))
+        });
+        registry.register("pooling", |p: PoolingParams| {
+            Ok(Box::new(Pooling::new(p.op)))
+        });
+        registry.register("convolution", |kernel: Kernel| {
+            kernel.validate()?;
+            Ok(Box::new(Convolution::with_kernel(kernel)))
+        });
+        registry.register("convolution_2p", |_: NoParams| {
+            Ok(Box::new(Convolution2Pass::new()))
+        });
+        registry.register("gaussian_blur", |blur: Blur| {
+            blur.validate()?;
+            Ok(Box::new(GaussianBlur::with_radius(blur.sigma, blur.radius)))
+        });
+        registry.register("roi", |p: RoiParams| {
+            Ok(Box::new(Roi::with_window(p.width, p.height, p.window)))
+        });
+        registry.register("sobel", |_: NoParams| Ok(Box::new(Sobel::new())));
+        registry.register("canny", |p: CannyParams| {
+            canny::Thresholds {
+                low: p.low,
+                high: p.high,
+            }
+            .validate()?;
+            Ok(Box::new(match p.iterations {
+                Some(iterations) => Canny::with_iterations(p.sigma, p.low, p.high, iterations),
+                None => Canny::new(p.sigma, p.low, p.high),
+            }))
+        });
+        registry.register("tracker", |p: TrackerParams| {
+            Ok(Box::new(match p.extent {
+                true => Tracker::with_extent(p.pooling, p.canvas),
+                false => Tracker::new(p.pooling, p.canvas),
+            }))
+        });
+        registry.register("label_tracker", |p: LabelTrackerParams| {
+            Ok(Box::new(LabelTracker::new(p.classes)))
+        });
+        registry.register("background_subtractor", |p: BackgroundSubtractorParams| {
+            let defaults = background_subtractor::Settings::for_model(p.model);
+            let settings = background_subtractor::Settings {
+                learning_rate: p.learning_rate.unwrap_or(defaults.learning_rate),
+                threshold: p.threshold.unwrap_or(defaults.threshold),
+            };
+            settings.validate()?;
+            Ok(Box::new(BackgroundSubtractor::with_settings(
+                p.model, settings,
+            )))
+        });
+        registry.register(
+            "frame_difference",
+            |settings: frame_difference::Settings| {
+                Ok(Box::new(FrameDifference::with_settings(settings)))
+            },
+        );
+        registry.register("not", |_: NoParams| Ok(Box::new(Not::new())));
+
+        registry
+    }
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+
+    // replaces any constructor previously registered for `kind`
+    pub fn register<P, F>(&mut self, kind: &str, constructor: F)
+    where
+        P: DeserializeOwned,
+        F: Fn(P) -> Result<Box<dyn ProcessingElement>, VkcvError> + 'static,
+    {
+        self.constructors.insert(
+            kind.to_string(),
+            Box::new(move |params| constructor(serde_json::from_value(params)?)),
+        );
+    }
+
+    pub fn kinds(&self) -> Vec<&str> {
+        let mut kinds: Vec<_> = self.constructors.keys().map(|k| k.as_str()).collect();
+        kinds.sort_unstable();
+        kinds
+    }
+
+    pub fn create(
+        &self,
+        element: &ElementDescription,
+    ) -> Result<Box<dyn ProcessingElement>, VkcvError> {
+        let constructor = self.constructors.get(&element.kind).ok_or_else(|| {
+            VkcvError::Description(format!(
+                "unknown element type '{}', expected one of {}",
+                element.kind,
+                self.kinds().join(", ")
+            ))
+        })?;
+
+        constructor(Value::Object(element.params.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::Param;
+
+    #[test]
+    fn color_tracker() {
+        let description = PipelineDescription::from_toml(COLOR_TRACKER).unwrap();
+        let elements = description.create(&Registry::default()).unwrap();
+        let names: Vec<_> = elements.iter().map(|pe| pe.name()).collect();
+
+        assert_eq!(
+            names,
+            [
+                "Hsvconv",
+                "ColorFilter",
+                "MorphologyEx",
+                "MorphologyEx",
+                "Pooling",
+                "Tracker"
+            ]
+        );
+
+        // round trip through json
+        let json = serde_json::to_string(&description).unwrap();
+        let description = PipelineDescription::from_json(&json).unwrap();
+        assert_eq!(description.elements.len(), 6);
+    }
+
+    #[test]
+    fn handles() {
+        let description = PipelineDescription::from_toml(COLOR_TRACKER_ROI).unwrap();
+        let elements = description.create(&Registry::default()).unwrap();
+
+        let window: Param<Window> = description.handle(&elements, "roi").unwrap().unwrap();
+        assert!(window.get().is_full());
+
+        // unknown name resp. wrong type
+        assert!(description
+            .handle::<Param<Window>>(&elements, "tracker")
+            .unwrap()
+            .is_none());
+        assert!(description
+            .handle::<Param<Vec<Range>>>(&elements, "roi")
+            .is_err());
+
+        let duplicate = PipelineDescription::from_toml(
+            "[[elements]]\ntype = \"hsvconv\"\nname = \"a\"\n[[elements]]\ntype = \"grayscale\"\nname = \"a\"",
+        )
+        .unwrap();
+        assert!(duplicate.create(&Registry::default()).is_err());
+    }
+
+    #[test]
+    fn multi_class() {
+        let description = PipelineDescription::from_toml(
+            r#"
+            [[elements]]
+            type = "hsvconv"
+
+            [[elements]]
+            type = "color_classifier"
+
+            [[elements.classes]]
+            name = "red"
+            range = { rgb_min = [0.9, 0.5, 0.5], rgb_max = [0.1, 1.0, 1.0] }
+
+            [[elements.classes]]
+            name = "green"
+            range = { rgb_min = [0.3, 0.5, 0.5], rgb_max = [0.4, 1.0, 1.0] }
+
+            [[elements]]
+            type = "label_tracker"
+            classes = 2
+            "#,
+        )
+        .unwrap();
+        let elements = description.create(&Registry::default()).unwrap();
+        let names: Vec<_> = elements.iter().map(|pe| pe.name()).collect();
+
+        assert_eq!(names, ["Hsvconv", "ColorClassifier", "LabelTracker"]);
+    }
+
+    #[test]
+    fn motion_tracker() {
+        let description = PipelineDescription::from_toml(
+            r#"
+            [[elements]]
+            type = "background_subtractor"
+            model = "gaussian"
+            threshold = 3.0
+
+            [[elements]]
+            type = "morphology_ex"
+            op = "open"
+
+            [[elements]]
+            type = "tracker"
+            pooling = "pooling4"
+            canvas = "pad"
+            "#,
+        )
+        .unwrap();
+        let elements = description.create(&Registry::default()).unwrap();
+        let names: Vec<_> = elements.iter().map(|pe| pe.name()).collect();
+
+        assert_eq!(names, ["BackgroundSubtractor", "MorphologyEx", "Tracker"]);
+    }
+
+    #[test]
+    fn invalid_elements() {
+        let registry = Registry::default();
+
+        let unknown = PipelineDescription::from_toml("[[elements]]\ntype = \"sharpen\"").unwrap();
+        assert!(unknown.create(&registry).is_err());
+
+        let typo = PipelineDescription::from_toml(
+            "[[elements]]\ntype = \"morphology\"\noperation = \"erode\"",
+        )
+        .unwrap();
+        assert!(typo.create(&registry).is_err());
+
+        let element = PipelineDescription::from_toml(
+            "[[elements]]\ntype = \"morphology\"\nop = \"erode\"\nelement = { shape = \"ellipse\", width = 5, height = 5 }",
+        )
+        .unwrap();
+        assert!(element.create(&registry).is_ok());
+
+        let thresholds = PipelineDescription::from_toml(
+            "[[elements]]\ntype = \"canny\"\nsigma = 1.0\nlow = 0.4\nhigh = 0.2",
+        )
+        .unwrap();
+        assert!(thresholds.create(&registry).is_err());
+
+        let learning_rate = PipelineDescription::from_toml(
+            "[[elements]]\ntype = \"background_subtractor\"\nlearning_rate = 1.5",
+        )
+        .unwrap();
+        assert!(learning_rate.create(&registry).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use vulkano::image::ImageAccess;
 
 use crate::{
+    error::VkcvError,
     processing_elements::{Io, IoFragment},
     utils::{self, ImageInfo},
 };
@@ -12,10 +13,10 @@ pub struct ImageDownload {
 }
 
 impl ImageDownload {
-    pub fn from_io(io: IoFragment) -> Result<Self, &'static str> {
+    pub fn from_io(io: IoFragment) -> Result<Self, VkcvError> {
         match &io.output {
             Io::Buffer(_) => {
-                let input_img = io.input_image().unwrap();
+                let input_img = io.input_image().ok_or(VkcvError::MissingImage)?;
 
                 let info = ImageInfo {
                     width: input_img.dimensions().width(),
@@ -29,7 +30,7 @@ impl ImageDownload {
                     buffer: Vec::new(),
                 })
             }
-            _ => Err("Output needs to be a buffer"),
+            _ => Err(VkcvError::MissingBuffer),
         }
     }
 
@@ -37,19 +38,19 @@ impl ImageDownload {
         &self.info
     }
 
-    pub fn transfer<'a>(&'a mut self) -> TransferredImage<'a> {
-        let buffer = self.io.output_buffer().unwrap();
-        let buffer_content = buffer.read().unwrap();
+    pub fn transfer<'a>(&'a mut self) -> Result<TransferredImage<'a>, VkcvError> {
+        let buffer = self.io.output_buffer().ok_or(VkcvError::MissingBuffer)?;
+        let buffer_content = buffer.read()?;
         if self.buffer.len() != buffer_content.len() {
             self.buffer.resize(buffer_content.len(), 0);
         }
 
         self.buffer.copy_from_slice(&buffer_content);
 
-        TransferredImage {
+        Ok(TransferredImage {
             buffer: &self.buffer,
             info: &self.info,
-        }
+        })
     }
 
     pub fn transferred_image<'a>(&'a self) -> TransferredImage<'a> {
@@ -70,8 +71,8 @@ impl<'a> TransferredImage<'a> {
         &self.buffer
     }
 
-    pub fn save_output_buffer(&self, filename: &str) {
-        utils::write_image(filename, &self.buffer_content(), &self.info);
+    pub fn save_output_buffer(&self, filename: &str) -> Result<(), VkcvError> {
+        utils::write_image(filename, &self.buffer_content(), &self.info)
     }
 
     pub fn info(&self) -> &'a ImageInfo {
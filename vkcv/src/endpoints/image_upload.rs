@@ -1,25 +1,32 @@
-use crate::processing_elements::{Io, IoFragment};
+use crate::{
+    error::VkcvError,
+    processing_elements::{Io, IoFragment},
+};
 
 pub struct ImageUpload {
     io: IoFragment,
 }
 
 impl ImageUpload {
-    pub fn from_io(io: IoFragment) -> Result<Self, &'static str> {
+    pub fn from_io(io: IoFragment) -> Result<Self, VkcvError> {
         match &io.input {
             Io::Buffer(_) => Ok(Self { io }),
-            _ => Err("Input needs to be a buffer"),
+            _ => Err(VkcvError::MissingBuffer),
         }
     }
 
-    pub fn copy_input_data(&self, data: &[u8]) {
-        if let Ok(mut lock) = self.io.input_buffer().as_mut().unwrap().write() {
-            let len = lock.len();
-            if len < data.len() {
-                lock.copy_from_slice(&data[0..len]);
-            } else {
-                lock.copy_from_slice(data);
-            }
+    pub fn copy_input_data(&self, data: &[u8]) -> Result<(), VkcvError> {
+        let buffer = self.io.input_buffer().ok_or(VkcvError::MissingBuffer)?;
+        let mut lock = buffer.write()?;
+        let len = lock.len();
+        if data.len() < len {
+            return Err(VkcvError::InvalidParameters(format!(
+                "expected {} bytes of image data, got {}",
+                len,
+                data.len()
+            )));
         }
+        lock.copy_from_slice(&data[0..len]);
+        Ok(())
     }
 }
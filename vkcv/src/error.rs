@@ -0,0 +1,146 @@
+use std::fmt;
+
+use vulkano::{
+    buffer::cpu_access::{ReadLockError, WriteLockError},
+    command_buffer::{
+        BuildError, ClearError, CommandBufferBeginError, CommandBufferExecError, CopyError,
+        PipelineExecutionError, QueryError,
+    },
+    descriptor_set::DescriptorSetCreationError,
+    device::DeviceCreationError,
+    format::Format,
+    image::{view::ImageViewCreationError, ImageError},
+    instance::InstanceCreationError,
+    memory::allocator::AllocationCreationError,
+    pipeline::compute::ComputePipelineCreationError,
+    query::{GetResultsError, QueryPoolCreateError},
+    sampler::SamplerCreationError,
+    shader::ShaderCreationError,
+    sync::FlushError,
+    LoadingError, OomError, VulkanError,
+};
+
+use crate::processing_elements::graph::GraphError;
+
+#[derive(Debug)]
+pub enum VkcvError {
+    Init(String),
+    ShaderLoad(String),
+    PipelineCreation(String),
+    DescriptorSet(String),
+    UnsupportedFormat(Format),
+    Allocation(String),
+    CommandBuffer(String),
+    Submission(String),
+    BufferAccess(String),
+    Query(String),
+    Description(String),
+    Io(String),
+    // e.g. a kernel of unsupported size
+    InvalidParameters(String),
+    // the upstream element did not provide an image resp. buffer
+    MissingImage,
+    MissingBuffer,
+    Graph(GraphError),
+    // wraps an error with the label of the element that failed
+    Element {
+        label: String,
+        source: Box<VkcvError>,
+    },
+}
+
+impl VkcvError {
+    pub fn in_element(self, label: &str) -> Self {
+        VkcvError::Element {
+            label: label.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for VkcvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VkcvError::Init(e) => write!(f, "vulkan initialization failed: {}", e),
+            VkcvError::ShaderLoad(e) => write!(f, "failed to load shader: {}", e),
+            VkcvError::PipelineCreation(e) => write!(f, "failed to create pipeline: {}", e),
+            VkcvError::DescriptorSet(e) => write!(f, "failed to create descriptor set: {}", e),
+            VkcvError::UnsupportedFormat(format) => write!(f, "unsupported format {:?}", format),
+            VkcvError::Allocation(e) => write!(f, "allocation failed: {}", e),
+            VkcvError::CommandBuffer(e) => write!(f, "failed to record command buffer: {}", e),
+            VkcvError::Submission(e) => write!(f, "failed to submit command buffer: {}", e),
+            VkcvError::BufferAccess(e) => write!(f, "failed to access buffer: {}", e),
+            VkcvError::Query(e) => write!(f, "timestamp query failed: {}", e),
+            VkcvError::Description(e) => write!(f, "invalid pipeline description: {}", e),
+            VkcvError::Io(e) => write!(f, "i/o error: {}", e),
+            VkcvError::InvalidParameters(e) => write!(f, "invalid parameters: {}", e),
+            VkcvError::MissingImage => write!(f, "expected an image from the upstream element"),
+            VkcvError::MissingBuffer => write!(f, "expected a buffer"),
+            VkcvError::Graph(e) => write!(f, "invalid pipeline graph: {}", e),
+            VkcvError::Element { label, source } => write!(f, "'{}': {}", label, source),
+        }
+    }
+}
+
+impl std::error::Error for VkcvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VkcvError::Graph(e) => Some(e),
+            VkcvError::Element { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident, $($error:ty),+) => {
+        $(
+            impl From<$error> for VkcvError {
+                fn from(e: $error) -> Self {
+                    VkcvError::$variant(e.to_string())
+                }
+            }
+        )+
+    };
+}
+
+impl_from!(
+    Init,
+    LoadingError,
+    InstanceCreationError,
+    DeviceCreationError,
+    VulkanError
+);
+impl_from!(ShaderLoad, ShaderCreationError);
+impl_from!(PipelineCreation, ComputePipelineCreationError);
+impl_from!(
+    DescriptorSet,
+    DescriptorSetCreationError,
+    ImageViewCreationError,
+    SamplerCreationError
+);
+impl_from!(Allocation, AllocationCreationError, ImageError, OomError);
+impl_from!(
+    CommandBuffer,
+    CommandBufferBeginError,
+    BuildError,
+    ClearError,
+    PipelineExecutionError,
+    CopyError
+);
+impl_from!(Submission, CommandBufferExecError, FlushError);
+impl_from!(BufferAccess, ReadLockError, WriteLockError);
+impl_from!(Query, QueryPoolCreateError, QueryError, GetResultsError);
+impl_from!(
+    Description,
+    toml::de::Error,
+    toml::ser::Error,
+    serde_json::Error
+);
+impl_from!(Io, std::io::Error, png::DecodingError, png::EncodingError);
+
+impl From<GraphError> for VkcvError {
+    fn from(e: GraphError) -> Self {
+        VkcvError::Graph(e)
+    }
+}
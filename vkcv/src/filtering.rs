@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+
+// 3D target position in camera coordinates
+pub type PointFilter = KalmanFilter<3>;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KalmanConfig {
+    // variance of the (white noise) acceleration, i.e. how quickly the target changes its velocity
+    pub process_noise: f32,
+    // variance of a single measurement
+    pub measurement_noise: f32,
+    // velocity variance of a new track
+    pub initial_velocity_variance: f32,
+    // measurements with a squared mahalanobis distance above are rejected as outliers
+    pub gate: f32,
+    // the track is dropped after predicting without measurements for longer (in seconds)
+    pub max_dropout: f32,
+    // accepted measurements until a new track is confirmed, i.e. locked
+    pub confirmations: u32,
+    // position standard deviation at which the confidence drops to 0.5
+    pub confidence_scale: f32,
+}
+
+impl Default for KalmanConfig {
+    fn default() -> Self {
+        // meters and seconds
+        Self {
+            process_noise: 1.0,
+            measurement_noise: 0.01,
+            initial_velocity_variance: 1.0,
+            // ~99.7% for 3 degrees of freedom
+            gate: 14.2,
+            max_dropout: 1.0,
+            confirmations: 3,
+            confidence_scale: 0.2,
+        }
+    }
+}
+
+// constant velocity model of a single axis
+#[derive(Clone, Copy, Debug)]
+struct Axis {
+    x: f32,
+    v: f32,
+    // covariance of (x, v)
+    p: [[f32; 2]; 2],
+}
+
+impl Axis {
+    fn new(x: f32, x_var: f32, v_var: f32) -> Self {
+        Self {
+            x,
+            v: 0.0,
+            p: [[x_var, 0.0], [0.0, v_var]],
+        }
+    }
+
+    fn predict(&mut self, dt: f32, q: f32) {
+        let p = self.p;
+        self.x += self.v * dt;
+
+        // P = F P F^T + Q, with the continuous white noise acceleration model
+        let p00 = p[0][0] + dt * (p[1][0] + p[0][1]) + dt * dt * p[1][1];
+        let p01 = p[0][1] + dt * p[1][1];
+        let p10 = p[1][0] + dt * p[1][1];
+        let p11 = p[1][1];
+
+        let dt2 = dt * dt;
+        self.p = [
+            [p00 + q * dt2 * dt / 3.0, p01 + q * dt2 / 2.0],
+            [p10 + q * dt2 / 2.0, p11 + q * dt],
+        ];
+    }
+
+    // residual and its variance
+    fn innovation(&self, z: f32, r: f32) -> (f32, f32) {
+        (z - self.x, self.p[0][0] + r)
+    }
+
+    fn correct(&mut self, y: f32, s: f32) {
+        let p = self.p;
+        let (k0, k1) = (p[0][0] / s, p[1][0] / s);
+
+        self.x += k0 * y;
+        self.v += k1 * y;
+
+        // P = (I - K H) P
+        self.p = [
+            [(1.0 - k0) * p[0][0], (1.0 - k0) * p[0][1]],
+            [p[1][0] - k1 * p[0][0], p[1][1] - k1 * p[0][1]],
+        ];
+    }
+}
+
+// Constant velocity Kalman filter over an N-dimensional position.
+//
+// The noise is assumed to be the same and independent on every axis, thus
+// the axes are filtered separately. A track is started by the first
+// measurement and dropped after `max_dropout` seconds without accepted
+// measurements, the position is predicted in between. The target is locked
+// once the track has been confirmed by `confirmations` measurements, before
+// a measurement rejected by the gate starts a new track instead.
+#[derive(Clone, Debug)]
+pub struct KalmanFilter<const N: usize> {
+    config: KalmanConfig,
+    axes: Option<[Axis; N]>,
+    // time since the last accepted measurement
+    since_update: f32,
+    // accepted measurements of the track
+    hits: u32,
+}
+
+impl<const N: usize> KalmanFilter<N> {
+    pub fn new(config: KalmanConfig) -> Self {
+        Self {
+            config,
+            axes: None,
+            since_update: 0.0,
+            hits: 0,
+        }
+    }
+
+    pub fn config(&self) -> &KalmanConfig {
+        &self.config
+    }
+
+    pub fn reset(&mut self) {
+        self.axes = None;
+        self.since_update = 0.0;
+        self.hits = 0;
+    }
+
+    // advances the state by dt seconds
+    pub fn predict(&mut self, dt: f32) -> Option<[f32; N]> {
+        if let Some(axes) = &mut self.axes {
+            for axis in axes.iter_mut() {
+                axis.predict(dt, self.config.process_noise);
+            }
+            self.since_update += dt;
+        }
+
+        // lost
+        if self.since_update > self.config.max_dropout {
+            self.axes = None;
+        }
+
+        self.position()
+    }
+
+    // returns false if the measurement has been rejected by the gate
+    pub fn update(&mut self, z: [f32; N]) -> bool {
+        let r = self.config.measurement_noise;
+        let confirmed = self.is_locked();
+
+        let axes = match &mut self.axes {
+            Some(axes) => axes,
+            None => {
+                self.start(z);
+                return true;
+            }
+        };
+
+        let innovations: [(f32, f32); N] = std::array::from_fn(|i| axes[i].innovation(z[i], r));
+
+        // squared mahalanobis distance
+        let d2: f32 = innovations.iter().map(|(y, s)| y * y / s).sum();
+        if d2 > self.config.gate {
+            // an unconfirmed track is likely a spurious detection
+            if !confirmed {
+                self.start(z);
+            }
+            return false;
+        }
+
+        for (axis, (y, s)) in axes.iter_mut().zip(innovations) {
+            axis.correct(y, s);
+        }
+        self.since_update = 0.0;
+        self.hits += 1;
+
+        true
+    }
+
+    fn start(&mut self, z: [f32; N]) {
+        let r = self.config.measurement_noise;
+        let v_var = self.config.initial_velocity_variance;
+
+        self.axes = Some(z.map(|x| Axis::new(x, r, v_var)));
+        self.since_update = 0.0;
+        self.hits = 1;
+    }
+
+    // predicts by dt and incorporates the measurement if there is one
+    pub fn step(&mut self, dt: f32, z: Option<[f32; N]>) -> Option<[f32; N]> {
+        self.predict(dt);
+        if let Some(z) = z {
+            self.update(z);
+        }
+        self.position()
+    }
+
+    pub fn position(&self) -> Option<[f32; N]> {
+        self.axes.map(|axes| axes.map(|axis| axis.x))
+    }
+
+    pub fn velocity(&self) -> Option<[f32; N]> {
+        self.axes.map(|axes| axes.map(|axis| axis.v))
+    }
+
+    // the mean position variance over all axes
+    pub fn variance(&self) -> Option<f32> {
+        self.axes
+            .map(|axes| axes.iter().map(|axis| axis.p[0][0]).sum::<f32>() / N as f32)
+    }
+
+    // in [0, 1], decreases as the position uncertainty grows, 0 without a
+    // confirmed track
+    pub fn confidence(&self) -> f32 {
+        match self.variance() {
+            Some(var) if self.is_locked() => {
+                1.0 / (1.0 + var / self.config.confidence_scale.powi(2))
+            }
+            _ => 0.0,
+        }
+    }
+
+    // a confirmed track exists, i.e. the target has been seen within `max_dropout`
+    pub fn is_locked(&self) -> bool {
+        self.axes.is_some() && self.hits >= self.config.confirmations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn constant_velocity() {
+        let mut filter = PointFilter::new(KalmanConfig::default());
+        assert!(!filter.is_locked());
+
+        // 1 m/s along x at 30 fps
+        let dt = 1.0 / 30.0;
+        for i in 0..60 {
+            filter.step(dt, Some([i as f32 * dt, 0.5, 2.0]));
+        }
+        assert!(filter.is_locked());
+        assert!((filter.velocity().unwrap()[0] - 1.0).abs() < 0.1);
+
+        // outliers are rejected
+        assert!(!filter.update([10.0, 0.5, 2.0]));
+
+        // keeps predicting and the lock during short dropouts
+        let p = filter.predict(0.5).unwrap();
+        assert!((p[0] - 2.5).abs() < 0.1);
+        assert!(filter.confidence() < 0.5);
+        assert!(filter.is_locked());
+
+        // and is lost eventually
+        assert!(filter.predict(1.0).is_none());
+        assert!(!filter.is_locked());
+        assert_eq!(filter.confidence(), 0.0);
+    }
+
+    #[test]
+    fn confirmation() {
+        let mut filter = PointFilter::new(KalmanConfig::default());
+        let dt = 1.0 / 30.0;
+
+        // a single (spurious) detection does not lock
+        filter.step(dt, Some([1.0, 0.5, 2.0]));
+        assert!(!filter.is_locked());
+        assert_eq!(filter.confidence(), 0.0);
+
+        // and is replaced by the actual target
+        assert!(!filter.update([-1.0, 0.0, 3.0]));
+        assert_eq!(filter.position(), Some([-1.0, 0.0, 3.0]));
+
+        for _ in 0..2 {
+            filter.step(dt, Some([-1.0, 0.0, 3.0]));
+        }
+        assert!(filter.is_locked());
+        assert!(filter.confidence() > 0.5);
+
+        // a reset requires a new confirmation
+        filter.reset();
+        filter.step(dt, Some([-1.0, 0.0, 3.0]));
+        assert!(!filter.is_locked());
+    }
+}
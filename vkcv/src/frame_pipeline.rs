@@ -0,0 +1,183 @@
+use std::{sync::Arc, time::Instant};
+
+use vulkano::sync::{self, FenceSignalFuture, GpuFuture};
+
+use crate::{
+    endpoints::{
+        image_download::{ImageDownload, TransferredImage},
+        image_upload::ImageUpload,
+    },
+    error::VkcvError,
+    image_pool::MemoryReport,
+    processing_elements::{IoFragment, Recording},
+    vk_init::VkContext,
+};
+
+// command buffer, input and output fragment as returned by `cv_pipeline_sequential`
+type Parts = (Recording, IoFragment, IoFragment);
+
+type Submission = Arc<FenceSignalFuture<Box<dyn GpuFuture + Send>>>;
+
+struct InFlight {
+    frame: u64,
+    submitted: Instant,
+    future: Submission,
+}
+
+struct Slot {
+    recording: Recording,
+    upload: ImageUpload,
+    download: ImageDownload,
+    in_flight: Option<InFlight>,
+}
+
+pub struct FrameResult<'a> {
+    pub frame: u64,
+    pub submitted: Instant,
+    // the time the result was found to be ready, i.e. an upper bound
+    pub completed: Instant,
+    pub image: TransferredImage<'a>,
+}
+
+// Keeps several frames in flight.
+//
+// Each slot holds its own copy of the pipeline (command buffer, images and
+// host buffers). While the GPU processes frame k, frame k+1 can be uploaded
+// and submitted to the next slot. Frame k+1 is executed after frame k, thus
+// the state of elements shared by the copies (see `StateImage`) sees every
+// frame in order. Results are handed back in frame order.
+pub struct FramePipeline {
+    slots: Vec<Slot>,
+    // the most recent submission, the next one waits for it
+    last: Option<Submission>,
+    next_frame: u64,
+    next_result: u64,
+}
+
+impl FramePipeline {
+    // `build` is called once per slot
+    pub fn new<F>(ctx: &VkContext, frames_in_flight: usize, mut build: F) -> Result<Self, VkcvError>
+    where
+        F: FnMut(&VkContext) -> Result<Parts, VkcvError>,
+    {
+        if frames_in_flight == 0 {
+            return Err(VkcvError::InvalidParameters(
+                "at least one frame in flight is required".to_string(),
+            ));
+        }
+
+        let slots = (0..frames_in_flight)
+            .map(|_| {
+                let (recording, input_io, output_io) = build(ctx)?;
+
+                Ok(Slot {
+                    recording,
+                    upload: ImageUpload::from_io(input_io)?,
+                    download: ImageDownload::from_io(output_io)?,
+                    in_flight: None,
+                })
+            })
+            .collect::<Result<_, VkcvError>>()?;
+
+        Ok(Self {
+            slots,
+            last: None,
+            next_frame: 0,
+            next_result: 0,
+        })
+    }
+
+    // the number of the frame submitted by the next `push`
+    pub fn next_frame(&self) -> u64 {
+        self.next_frame
+    }
+
+    // the storage images of all slots
+    pub fn memory(&self) -> MemoryReport {
+        self.slots
+            .iter()
+            .map(|s| s.recording.memory())
+            .fold(MemoryReport::default(), |a, b| a + b)
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.slots.iter().filter(|s| s.in_flight.is_some()).count()
+    }
+
+    // Uploads and submits a frame. If all slots are busy, this blocks until the
+    // oldest frame is finished and returns its result.
+    pub fn push(
+        &mut self,
+        ctx: &VkContext,
+        data: &[u8],
+    ) -> Result<Option<FrameResult<'_>>, VkcvError> {
+        let index = (self.next_frame % self.slots.len() as u64) as usize;
+
+        // make room for the new frame
+        let finished = match self.slots[index].in_flight.take() {
+            Some(in_flight) => Some(self.collect(index, in_flight)?),
+            None => None,
+        };
+
+        let slot = &mut self.slots[index];
+        slot.upload.copy_input_data(data)?;
+
+        let after = match self.last.take() {
+            Some(last) => last.boxed_send(),
+            None => sync::now(ctx.device.clone()).boxed_send(),
+        };
+        let future = Arc::new(slot.recording.submit_after(ctx, after)?);
+        self.last = Some(future.clone());
+
+        slot.in_flight = Some(InFlight {
+            frame: self.next_frame,
+            submitted: Instant::now(),
+            future,
+        });
+        self.next_frame += 1;
+
+        Ok(finished.map(|(frame, submitted, completed)| FrameResult {
+            frame,
+            submitted,
+            completed,
+            image: self.slots[index].download.transferred_image(),
+        }))
+    }
+
+    // blocks until the oldest frame in flight is finished and returns its result,
+    // e.g. to drain the pipeline
+    pub fn pop(&mut self) -> Result<Option<FrameResult<'_>>, VkcvError> {
+        if self.next_result == self.next_frame {
+            return Ok(None);
+        }
+
+        let index = (self.next_result % self.slots.len() as u64) as usize;
+        let in_flight = self.slots[index].in_flight.take().unwrap();
+        let (frame, submitted, completed) = self.collect(index, in_flight)?;
+
+        Ok(Some(FrameResult {
+            frame,
+            submitted,
+            completed,
+            image: self.slots[index].download.transferred_image(),
+        }))
+    }
+
+    fn collect(
+        &mut self,
+        index: usize,
+        in_flight: InFlight,
+    ) -> Result<(u64, Instant, Instant), VkcvError> {
+        debug_assert_eq!(in_flight.frame, self.next_result);
+
+        // the frame is done with, also if its result is lost
+        self.next_result += 1;
+
+        in_flight.future.wait(None)?;
+        let completed = Instant::now();
+
+        self.slots[index].download.transfer()?;
+
+        Ok((in_flight.frame, in_flight.submitted, completed))
+    }
+}
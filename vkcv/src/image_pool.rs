@@ -0,0 +1,209 @@
+use std::{fmt, ops, sync::Arc};
+
+use vulkano::image::StorageImage;
+
+use crate::{
+    error::VkcvError,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+// Hands out storage images while a pipeline is built.
+//
+// Images released after their last use in the command buffer are handed out
+// again to later requests of the same size and format. As the command buffer
+// is executed in recording order, and vulkano inserts the barriers between
+// the stages, subsequent stages can safely share these allocations.
+pub struct ImagePool {
+    free: FreeList<StorageImage>,
+    report: MemoryReport,
+}
+
+impl ImagePool {
+    pub fn new(reuse: bool) -> Self {
+        Self {
+            free: FreeList::new(reuse),
+            report: MemoryReport::default(),
+        }
+    }
+
+    pub fn acquire(
+        &mut self,
+        ctx: &VkContext,
+        info: &ImageInfo,
+    ) -> Result<Arc<StorageImage>, VkcvError> {
+        let bytes = info.bytes_count() as u64;
+        self.report.images_requested += 1;
+        self.report.bytes_requested += bytes;
+
+        if let Some(img) = self.free.take(info) {
+            return Ok(img);
+        }
+
+        self.report.images_allocated += 1;
+        self.report.bytes_allocated += bytes;
+
+        utils::allocate_storage_image(ctx, info)
+    }
+
+    // the image must not be used by any command recorded after this call
+    pub fn release(&mut self, image: Arc<StorageImage>) {
+        self.free.put(ImageInfo::from(&image), image);
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        self.report
+    }
+}
+
+// the released images, nothing is kept unless `reuse` is set
+struct FreeList<T> {
+    reuse: bool,
+    items: Vec<(ImageInfo, Arc<T>)>,
+}
+
+impl<T> FreeList<T> {
+    fn new(reuse: bool) -> Self {
+        Self {
+            reuse,
+            items: Vec::new(),
+        }
+    }
+
+    fn take(&mut self, info: &ImageInfo) -> Option<Arc<T>> {
+        let i = self.items.iter().position(|(i, _)| i == info)?;
+        Some(self.items.swap_remove(i).1)
+    }
+
+    // an item released twice is handed out once
+    fn put(&mut self, info: ImageInfo, item: Arc<T>) {
+        if self.reuse && !self.items.iter().any(|(_, i)| Arc::ptr_eq(i, &item)) {
+            self.items.push((info, item));
+        }
+    }
+}
+
+// The lifetimes of the outputs of the nodes of a graph, i.e. the position of
+// the last node reading each output in the recording `order`, `None` if it is
+// not read at all. `inputs` lists the upstream nodes of every node.
+pub(crate) fn last_uses(inputs: &[Vec<usize>], order: &[usize]) -> Vec<Option<usize>> {
+    let mut last_use = vec![None; inputs.len()];
+    for (pos, node) in order.iter().enumerate() {
+        for upstream in &inputs[*node] {
+            last_use[*upstream] = Some(pos);
+        }
+    }
+    last_use
+}
+
+// storage images requested by the elements of a pipeline vs. actually allocated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub images_requested: usize,
+    pub images_allocated: usize,
+    pub bytes_requested: u64,
+    pub bytes_allocated: u64,
+}
+
+impl ops::Add for MemoryReport {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            images_requested: self.images_requested + other.images_requested,
+            images_allocated: self.images_allocated + other.images_allocated,
+            bytes_requested: self.bytes_requested + other.bytes_requested,
+            bytes_allocated: self.bytes_allocated + other.bytes_allocated,
+        }
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+        write!(
+            f,
+            "{} images ({:.2} MiB) requested, {} images ({:.2} MiB) allocated",
+            self.images_requested,
+            mib(self.bytes_requested),
+            self.images_allocated,
+            mib(self.bytes_allocated)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vulkano::format::Format;
+
+    fn info(width: u32) -> ImageInfo {
+        ImageInfo {
+            width,
+            height: 4,
+            format: Format::R8_UNORM,
+        }
+    }
+
+    #[test]
+    fn lifetimes() {
+        // 0 -> 1 -> 3 and 0 -> 2 -> 3, 2 is recorded before 1
+        let inputs = vec![vec![], vec![0], vec![0], vec![1, 2]];
+        let last_use = last_uses(&inputs, &[0, 2, 1, 3]);
+        assert_eq!(last_use, [Some(2), Some(3), Some(3), None]);
+    }
+
+    #[test]
+    fn reuse() {
+        let mut free = FreeList::new(true);
+        let (a, b) = (Arc::new(0), Arc::new(1));
+
+        free.put(info(4), a.clone());
+        free.put(info(4), a.clone());
+        free.put(info(8), b.clone());
+
+        // only handed out to a request of the same size and format
+        assert!(free.take(&info(2)).is_none());
+        assert!(Arc::ptr_eq(&free.take(&info(4)).unwrap(), &a));
+        assert!(free.take(&info(4)).is_none());
+        assert!(Arc::ptr_eq(&free.take(&info(8)).unwrap(), &b));
+
+        let mut free = FreeList::new(false);
+        free.put(info(4), a);
+        assert!(free.take(&info(4)).is_none());
+    }
+
+    #[test]
+    fn ping_pong() {
+        // a chain of 4 stages with equally sized outputs alternates between
+        // two images
+        let inputs = vec![vec![], vec![0], vec![1], vec![2]];
+        let order = [0, 1, 2, 3];
+        let last_use = last_uses(&inputs, &order);
+
+        let mut free = FreeList::new(true);
+        let mut outputs: Vec<Arc<usize>> = vec![];
+        let mut allocated = 0;
+
+        for (pos, node) in order.iter().enumerate() {
+            let output = free.take(&info(4)).unwrap_or_else(|| {
+                allocated += 1;
+                Arc::new(allocated)
+            });
+            outputs.push(output);
+
+            for upstream in &inputs[*node] {
+                if last_use[*upstream] == Some(pos) {
+                    free.put(info(4), outputs[*upstream].clone());
+                }
+            }
+        }
+
+        assert_eq!(allocated, 2);
+        assert_eq!(
+            outputs.iter().map(|o| **o).collect::<Vec<_>>(),
+            [1, 2, 1, 2]
+        );
+    }
+}
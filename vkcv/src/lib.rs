@@ -1,7 +1,16 @@
+pub mod description;
 pub mod draw;
 pub mod endpoints;
+pub mod error;
+pub mod filtering;
+pub mod frame_pipeline;
+pub mod image_pool;
+pub mod params;
+pub mod pipeline_cache;
 pub mod processing_elements;
+pub mod profiler;
 pub mod realsense;
+pub mod state_image;
 pub mod utils;
 pub mod vk_init;
 pub use vulkano;
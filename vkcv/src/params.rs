@@ -0,0 +1,196 @@
+use std::sync::{Arc, Mutex, Weak};
+
+use vulkano::buffer::{cpu_access::WriteLockError, BufferUsage, CpuAccessibleBuffer};
+
+use crate::{error::VkcvError, processing_elements::AutoCommandBufferBuilder, vk_init::VkContext};
+
+// parameters of an element, encoded as expected by the uniform block of its shader (std140)
+pub trait Parameters: Clone + Send + 'static {
+    fn encode(&self) -> Vec<u8>;
+
+    // rejects values the shader cannot handle, checked before a value is set
+    fn validate(&self) -> Result<(), VkcvError> {
+        Ok(())
+    }
+}
+
+type Check<T> = Box<dyn Fn(&T) -> Result<(), VkcvError> + Send>;
+
+struct Binding<T> {
+    buffer: Weak<CpuAccessibleBuffer<[u8]>>,
+    // generation of the value in the buffer
+    generation: u64,
+    // restrictions of the pipeline the buffer is bound to, e.g. a separable kernel
+    check: Option<Check<T>>,
+}
+
+struct Shared<T> {
+    value: T,
+    generation: u64,
+    // one uniform buffer for every pipeline the element was built into
+    bindings: Vec<Binding<T>>,
+}
+
+// A handle to the parameters of an element.
+//
+// Every pipeline the element is built into gets its own uniform buffer. `set`
+// writes the new value to all buffers which are not in use by the gpu, the
+// others are updated right before the next submission of their pipeline (see
+// `Recording::submit`). Thus the change is picked up without re-recording the
+// command buffer, also with several frames in flight.
+pub struct Param<T: Parameters> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T: Parameters> Clone for Param<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Parameters> Param<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                value,
+                generation: 0,
+                bindings: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn get(&self) -> T {
+        self.shared.lock().unwrap().value.clone()
+    }
+
+    // the value is only changed if it is accepted by every pipeline
+    pub fn set(&self, value: T) -> Result<(), VkcvError> {
+        value.validate()?;
+
+        let mut shared = self.shared.lock().unwrap();
+
+        // forget about buffers of dropped pipelines
+        shared
+            .bindings
+            .retain(|binding| binding.buffer.strong_count() > 0);
+
+        for check in shared.bindings.iter().filter_map(|b| b.check.as_ref()) {
+            check(&value)?;
+        }
+
+        // lock all buffers before writing any, buffers in use by the gpu are
+        // written before their next submission
+        let buffers: Vec<_> = shared
+            .bindings
+            .iter()
+            .map(|binding| binding.buffer.upgrade())
+            .collect();
+
+        let mut locks = Vec::new();
+        for (i, buffer) in buffers.iter().enumerate() {
+            if let Some(buffer) = buffer {
+                match buffer.write() {
+                    Ok(lock) => locks.push((i, lock)),
+                    Err(WriteLockError::GpuLocked) => (),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        let bytes = value.encode();
+        let generation = shared.generation + 1;
+        for (i, mut lock) in locks {
+            lock.copy_from_slice(&bytes);
+            shared.bindings[i].generation = generation;
+        }
+        shared.generation = generation;
+        shared.value = value;
+
+        Ok(())
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut T)) -> Result<(), VkcvError> {
+        let mut value = self.get();
+        f(&mut value);
+        self.set(value)
+    }
+
+    // creates a uniform buffer holding the current value, used when building the element
+    pub(crate) fn bind(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+    ) -> Result<Arc<CpuAccessibleBuffer<[u8]>>, VkcvError> {
+        self.bind_checked(ctx, builder, None)
+    }
+
+    // like `bind`, later values are additionally required to pass `check` as
+    // long as the pipeline exists
+    pub(crate) fn bind_with(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        check: impl Fn(&T) -> Result<(), VkcvError> + Send + 'static,
+    ) -> Result<Arc<CpuAccessibleBuffer<[u8]>>, VkcvError> {
+        self.bind_checked(ctx, builder, Some(Box::new(check)))
+    }
+
+    fn bind_checked(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        check: Option<Check<T>>,
+    ) -> Result<Arc<CpuAccessibleBuffer<[u8]>>, VkcvError> {
+        let mut shared = self.shared.lock().unwrap();
+
+        // the initial value has not been validated yet
+        shared.value.validate()?;
+        if let Some(check) = &check {
+            check(&shared.value)?;
+        }
+
+        let buffer = CpuAccessibleBuffer::from_iter(
+            &ctx.memory.allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..Default::default()
+            },
+            true,
+            shared.value.encode(),
+        )?;
+        let generation = shared.generation;
+        shared.bindings.push(Binding {
+            buffer: Arc::downgrade(&buffer),
+            generation,
+            check,
+        });
+
+        let param = self.clone();
+        let bound = buffer.clone();
+        builder.on_submit(move || param.sync(&bound));
+
+        Ok(buffer)
+    }
+
+    // writes the current value if it was set while the buffer was in use
+    fn sync(&self, buffer: &Arc<CpuAccessibleBuffer<[u8]>>) -> Result<(), VkcvError> {
+        let mut guard = self.shared.lock().unwrap();
+        let shared = &mut *guard;
+
+        let binding = shared
+            .bindings
+            .iter_mut()
+            .find(|binding| binding.buffer.as_ptr() == Arc::as_ptr(buffer));
+
+        if let Some(binding) = binding {
+            if binding.generation != shared.generation {
+                buffer.write()?.copy_from_slice(&shared.value.encode());
+                binding.generation = shared.generation;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use vulkano::{
+    device::Device,
+    pipeline::{cache::PipelineCache as VkPipelineCache, ComputePipeline},
+    shader::{ShaderCreationError, ShaderModule, SpecializationConstants},
+};
+
+use crate::error::VkcvError;
+
+// every module generated by `vulkano_shaders::shader!` has its own
+// specialization constants type, hence it identifies the shader
+type Key = (TypeId, Vec<u8>);
+
+// Compute pipelines created so far, keyed by shader and specialization constants.
+//
+// Building an element twice, or an element dispatching the same shader
+// several times, takes the pipeline from here. New pipelines are created
+// through a vulkan pipeline cache, which can be saved to disk to speed up
+// the next start.
+pub struct PipelineCache {
+    device: Arc<Device>,
+    cache: Mutex<Arc<VkPipelineCache>>,
+    pipelines: Mutex<HashMap<Key, Arc<ComputePipeline>>>,
+}
+
+impl PipelineCache {
+    pub(crate) fn new(device: Arc<Device>) -> Result<Self, VkcvError> {
+        Ok(Self {
+            cache: Mutex::new(VkPipelineCache::empty(device.clone())?),
+            device,
+            pipelines: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // `load` is only called if there is no such pipeline yet
+    pub fn get_or_create<S, L>(&self, load: L, spec: &S) -> Result<Arc<ComputePipeline>, VkcvError>
+    where
+        S: SpecializationConstants + 'static,
+        L: FnOnce(Arc<Device>) -> Result<Arc<ShaderModule>, ShaderCreationError>,
+    {
+        let key = (TypeId::of::<S>(), spec_bytes(spec));
+        if let Some(pipeline) = self.pipelines.lock().unwrap().get(&key) {
+            return Ok(pipeline.clone());
+        }
+
+        let shader = load(self.device.clone())?;
+        let entry_point = shader
+            .entry_point("main")
+            .ok_or_else(|| VkcvError::ShaderLoad("missing entry point 'main'".to_string()))?;
+
+        let cache = self.cache.lock().unwrap().clone();
+        let pipeline =
+            ComputePipeline::new(self.device.clone(), entry_point, spec, Some(cache), |_| {})?;
+
+        self.pipelines.lock().unwrap().insert(key, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Replaces the vulkan pipeline cache by the data written by `save`,
+    // returns false if there is no such file. Should be called before any
+    // pipeline is built.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<bool, VkcvError> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+
+        // the driver checks the header (vendor, device and cache uuid) and
+        // ignores data written by another device or driver version
+        let cache = unsafe { VkPipelineCache::with_data(self.device.clone(), &data)? };
+        *self.cache.lock().unwrap() = cache;
+
+        Ok(true)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), VkcvError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let data = self.cache.lock().unwrap().get_data()?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    // $XDG_CACHE_HOME/vkcv/pipelines.bin resp. ~/.cache/vkcv/pipelines.bin
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+
+        Some(dir.join("vkcv").join("pipelines.bin"))
+    }
+
+    // for applications: failures are only reported, a missing cache
+    // just slows down the start
+    pub fn load_default(&self) {
+        if let Some(path) = Self::default_path() {
+            if let Err(e) = self.load(&path) {
+                println!(
+                    ">> Failed to load pipeline cache '{}': {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    pub fn save_default(&self) {
+        if let Some(path) = Self::default_path() {
+            if let Err(e) = self.save(&path) {
+                println!(
+                    ">> Failed to save pipeline cache '{}': {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+// the values of the constants, without any padding
+fn spec_bytes<S: SpecializationConstants>(spec: &S) -> Vec<u8> {
+    let base = spec as *const S as *const u8;
+
+    S::descriptors()
+        .iter()
+        .flat_map(|entry| {
+            // the entries describe the fields of `S`
+            unsafe { std::slice::from_raw_parts(base.add(entry.offset as usize), entry.size) }
+        })
+        .copied()
+        .collect()
+}
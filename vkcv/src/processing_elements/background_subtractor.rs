@@ -0,0 +1,230 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::{ClearColorValue, Format},
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    state_image::SharedState,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
+
+mod cs_r8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/background_subtractor.comp.glsl",
+        define: [("FORMAT", "r8"), ("CHANNELS", "1")],
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod cs_rgba8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/background_subtractor.comp.glsl",
+        define: [("FORMAT", "rgba8"), ("CHANNELS", "3")],
+    }
+}
+
+const FORMATS: [Format; 2] = [Format::R8_UNORM, Format::R8G8B8A8_UNORM];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Model {
+    // foreground if any channel differs from the mean by more than the threshold
+    #[default]
+    RunningAverage,
+    // foreground if the difference exceeds threshold standard deviations of the pixel
+    Gaussian,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    // weight of the current frame in the background model, in [0, 1]
+    pub learning_rate: f32,
+    pub threshold: f32,
+}
+
+impl Settings {
+    pub fn for_model(model: Model) -> Self {
+        match model {
+            Model::RunningAverage => Self {
+                learning_rate: 0.05,
+                threshold: 0.1,
+            },
+            Model::Gaussian => Self {
+                learning_rate: 0.05,
+                threshold: 2.5,
+            },
+        }
+    }
+}
+
+impl Parameters for Settings {
+    fn encode(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&cs_r8::ty::Params {
+            learning_rate: self.learning_rate,
+            threshold: self.threshold,
+        })
+        .to_vec()
+    }
+
+    fn validate(&self) -> Result<(), VkcvError> {
+        // also rejects NaN
+        if !(0.0..=1.0).contains(&self.learning_rate) {
+            return Err(VkcvError::InvalidParameters(format!(
+                "learning rate must be in [0, 1], got {}",
+                self.learning_rate
+            )));
+        }
+        if !(self.threshold >= 0.0 && self.threshold.is_finite()) {
+            return Err(VkcvError::InvalidParameters(format!(
+                "threshold must not be negative, got {}",
+                self.threshold
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Detects moving objects by comparing each frame to a background model.
+//
+// The model is kept in a state image shared by all pipelines the element is
+// built into and updated by every frame, the output is a binary r8 mask.
+// After a `reset`, the model starts over with the next frame.
+pub struct BackgroundSubtractor {
+    model: Model,
+    settings: Param<Settings>,
+    state: SharedState,
+}
+
+impl BackgroundSubtractor {
+    pub fn new(model: Model) -> Self {
+        Self::with_settings(model, Settings::for_model(model))
+    }
+
+    pub fn with_settings(model: Model, settings: Settings) -> Self {
+        Self {
+            model,
+            settings: Param::new(settings),
+            state: SharedState::new(),
+        }
+    }
+
+    // learning rate and threshold can be changed after the pipeline has been built
+    pub fn params(&self) -> Param<Settings> {
+        self.settings.clone()
+    }
+}
+
+impl ProcessingElement for BackgroundSubtractor {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let format = input_img.format();
+
+        let gaussian = (self.model == Model::Gaussian) as i32;
+        let pipeline = match format {
+            Format::R8_UNORM => utils::compute_pipeline(
+                ctx,
+                cs_r8::load,
+                &cs_r8::SpecializationConstants { gaussian },
+            )?,
+            Format::R8G8B8A8_UNORM => utils::compute_pipeline(
+                ctx,
+                cs_rgba8::load,
+                &cs_rgba8::SpecializationConstants { gaussian },
+            )?,
+            _ => return Err(VkcvError::UnsupportedFormat(format)),
+        };
+
+        // output image
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo::from_image(&input_img, Format::R8_UNORM),
+        )?;
+
+        // a variance of 0 marks pixels which have not been seen yet
+        let model_img = self.state.get_or_create(
+            ctx,
+            &ImageInfo::from_image(&input_img, Format::R32G32B32A32_SFLOAT),
+            ClearColorValue::Float([0.0; 4]),
+        )?;
+
+        // setup layout
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, input_img_view),
+                WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::image_view(2, model_img.view()?),
+                WriteDescriptorSet::buffer(3, self.settings.bind(ctx, builder)?),
+                WriteDescriptorSet::buffer(4, model_img.bind_reset(ctx, builder)?),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(
+                &input_img.dimensions().width_height(),
+                &[16, 16],
+            ))?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("BackgroundSubtractor", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &FORMATS
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            format: Format::R8_UNORM,
+            ..info
+        })
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+
+    fn reset(&self) {
+        self.state.reset();
+    }
+}
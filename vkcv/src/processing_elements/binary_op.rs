@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess, StorageImage},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{error::VkcvError, utils, vk_init::VkContext};
+
+use super::{AutoCommandBufferBuilder, Io, IoFragment, MultiInputElement, ProcessingElement};
+
+mod cs_r8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/binary_op.comp.glsl",
+        define: [("FORMAT", "r8")],
+    }
+}
+
+mod cs_rgba8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/binary_op.comp.glsl",
+        define: [("FORMAT", "rgba8")],
+    }
+}
+
+pub const A: &str = "a";
+pub const B: &str = "b";
+
+const FORMATS: [Format; 2] = [Format::R8_UNORM, Format::R8G8B8A8_UNORM];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Operation {
+    Add,
+    Subtract,
+    AbsDiff,
+    Multiply,
+    And,
+    Or,
+    Min,
+    Max,
+}
+
+impl Operation {
+    pub(crate) fn code(&self) -> i32 {
+        match self {
+            Operation::Add => 0,
+            Operation::Subtract => 1,
+            Operation::AbsDiff => 2,
+            Operation::Multiply => 3,
+            Operation::And => 4,
+            Operation::Or => 5,
+            Operation::Min => 7,
+            Operation::Max => 8,
+        }
+    }
+}
+
+// combines the images connected to the ports `A` and `B` pixel by pixel
+// both images are required to have the same size and format (r8 or rgba8)
+pub struct BinaryOp {
+    op: Operation,
+}
+
+impl BinaryOp {
+    pub fn new(op: Operation) -> Self {
+        Self { op }
+    }
+}
+
+impl MultiInputElement for BinaryOp {
+    fn input_ports(&self) -> &[&'static str] {
+        &[A, B]
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &FORMATS
+    }
+
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        inputs: &[&IoFragment],
+    ) -> Result<IoFragment, VkcvError> {
+        // input images
+        let input_img_a = inputs[0].output_image().ok_or(VkcvError::MissingImage)?;
+        let input_img_b = inputs[1].output_image().ok_or(VkcvError::MissingImage)?;
+
+        let size_a = input_img_a.dimensions().width_height();
+        let size_b = input_img_b.dimensions().width_height();
+        if size_a != size_b {
+            return Err(VkcvError::InvalidParameters(format!(
+                "inputs differ in size, {:?} and {:?}",
+                size_a, size_b
+            )));
+        }
+        if input_img_a.format() != input_img_b.format() {
+            return Err(VkcvError::UnsupportedFormat(input_img_b.format()));
+        }
+
+        let output_img = record(ctx, builder, self.op.code(), &input_img_a, &input_img_b)?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img_a),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label(&format!("BinaryOp ({:?})", self.op), &output_img),
+        })
+    }
+}
+
+// bitwise not, e.g. to invert a mask
+pub struct Not {}
+
+impl Default for Not {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Not {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ProcessingElement for Not {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+
+        // the second operand is ignored by the shader
+        let output_img = record(ctx, builder, 6, &input_img, &input_img)?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("Not", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &FORMATS
+    }
+}
+
+// records `op` on the two images and returns the result
+pub(crate) fn record(
+    ctx: &VkContext,
+    builder: &mut AutoCommandBufferBuilder,
+    op: i32,
+    input_img_a: &Arc<StorageImage>,
+    input_img_b: &Arc<StorageImage>,
+) -> Result<Arc<StorageImage>, VkcvError> {
+    let local_size = 16;
+
+    let pipeline = match input_img_a.format() {
+        Format::R8_UNORM => utils::compute_pipeline(
+            ctx,
+            cs_r8::load,
+            &cs_r8::SpecializationConstants {
+                constant_0: local_size,
+                constant_1: local_size,
+                op,
+            },
+        )?,
+        Format::R8G8B8A8_UNORM => utils::compute_pipeline(
+            ctx,
+            cs_rgba8::load,
+            &cs_rgba8::SpecializationConstants {
+                constant_0: local_size,
+                constant_1: local_size,
+                op,
+            },
+        )?,
+        format => return Err(VkcvError::UnsupportedFormat(format)),
+    };
+
+    // output image
+    let output_img = utils::create_storage_image(ctx, builder, &input_img_a.into())?;
+
+    // setup layout
+    let layout = pipeline.layout().set_layouts().get(0).unwrap();
+    let input_img_a_view = ImageView::new_default(input_img_a.clone())?;
+    let input_img_b_view = ImageView::new_default(input_img_b.clone())?;
+    let output_img_view = ImageView::new_default(output_img.clone())?;
+
+    let set = PersistentDescriptorSet::new(
+        &ctx.memory.descriptor_set_allocator,
+        layout.clone(),
+        [
+            WriteDescriptorSet::image_view(0, input_img_a_view),
+            WriteDescriptorSet::image_view(1, input_img_b_view),
+            WriteDescriptorSet::image_view(2, output_img_view),
+        ],
+    )?;
+
+    // build command buffer
+    builder
+        .bind_pipeline_compute(pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            pipeline.layout().clone(),
+            0,
+            set,
+        )
+        .dispatch(utils::workgroups(
+            &input_img_a.dimensions().width_height(),
+            &[local_size, local_size],
+        ))?;
+
+    Ok(output_img)
+}
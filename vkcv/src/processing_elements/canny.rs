@@ -0,0 +1,236 @@
+use std::{any::Any, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess, StorageImage},
+    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{
+    gaussian_blur::GaussianBlur, sobel::Sobel, AutoCommandBufferBuilder, Io, IoFragment,
+    ProcessingElement,
+};
+
+mod cs_nms {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/canny_nms.comp.glsl",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod cs_hysteresis {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/canny_hysteresis.comp.glsl",
+    }
+}
+
+// see canny_hysteresis.comp.glsl
+const TILE_SIZE: u32 = 16;
+
+// thresholds on the gradient magnitude, which is in [0, 4 sqrt(2)] for
+// intensities in [0, 1]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    pub low: f32,
+    pub high: f32,
+}
+
+impl Parameters for Thresholds {
+    fn encode(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&cs_nms::ty::Params {
+            low: self.low,
+            high: self.high,
+        })
+        .to_vec()
+    }
+
+    fn validate(&self) -> Result<(), VkcvError> {
+        // also rejects NaN
+        if !(0.0 <= self.low && self.low <= self.high && self.high.is_finite()) {
+            return Err(VkcvError::InvalidParameters(format!(
+                "expected 0 <= low <= high, got low {} and high {}",
+                self.low, self.high
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Canny edge detector producing a binary r8 mask.
+//
+// The image is blurred (skipped if sigma is 0), followed by the sobel
+// operator, non-maximum suppression and double thresholding. Weak edges are
+// then promoted to strong edges if connected to a strong edge. Within a tile
+// of 16x16 pixels this is iterated until convergence, across tiles once per
+// iteration, i.e. an edge is followed through at least `iterations` tiles.
+pub struct Canny {
+    blur: Option<GaussianBlur>,
+    sobel: Sobel,
+    thresholds: Param<Thresholds>,
+    iterations: u32,
+}
+
+impl Canny {
+    pub fn new(sigma: f32, low: f32, high: f32) -> Self {
+        Self::with_iterations(sigma, low, high, 4)
+    }
+
+    pub fn with_iterations(sigma: f32, low: f32, high: f32, iterations: u32) -> Self {
+        Self {
+            blur: (sigma > 0.0).then(|| GaussianBlur::new(sigma)),
+            sobel: Sobel::new(),
+            thresholds: Param::new(Thresholds { low, high }),
+            iterations: iterations.max(1),
+        }
+    }
+
+    // the thresholds can be changed after the pipeline has been built
+    pub fn params(&self) -> Param<Thresholds> {
+        self.thresholds.clone()
+    }
+
+    fn hysteresis_pipeline(
+        ctx: &VkContext,
+        final_pass: bool,
+    ) -> Result<Arc<ComputePipeline>, VkcvError> {
+        utils::compute_pipeline(
+            ctx,
+            cs_hysteresis::load,
+            &cs_hysteresis::SpecializationConstants {
+                final_pass: final_pass as i32,
+            },
+        )
+    }
+}
+
+impl ProcessingElement for Canny {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        let local_size = 16;
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let info = ImageInfo::from(&input_img);
+
+        // blur and gradients
+        let blurred = match &self.blur {
+            Some(blur) => Some(blur.build(ctx, builder, input)?),
+            None => None,
+        };
+        let gradients = self
+            .sobel
+            .build(ctx, builder, blurred.as_ref().unwrap_or(input))?;
+        let gradients_img = gradients.output_image().ok_or(VkcvError::MissingImage)?;
+
+        if let Some(blurred_img) = blurred.and_then(|b| b.output_image()) {
+            utils::release_storage_image(builder, blurred_img);
+        }
+
+        // non-maximum suppression and double thresholding
+        let nms_pipeline = utils::compute_pipeline(
+            ctx,
+            cs_nms::load,
+            &cs_nms::SpecializationConstants {
+                constant_0: local_size,
+                constant_1: local_size,
+            },
+        )?;
+
+        // hysteresis ping-pongs between two images
+        let mut images: [Arc<StorageImage>; 2] = [
+            utils::create_storage_image(ctx, builder, &info)?,
+            utils::create_storage_image(ctx, builder, &info)?,
+        ];
+
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            nms_pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::image_view(0, ImageView::new_default(gradients_img.clone())?),
+                WriteDescriptorSet::image_view(1, ImageView::new_default(images[0].clone())?),
+                WriteDescriptorSet::buffer(2, self.thresholds.bind(ctx, builder)?),
+            ],
+        )?;
+
+        builder
+            .bind_pipeline_compute(nms_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                nms_pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(
+                &input_img.dimensions().width_height(),
+                &[local_size, local_size],
+            ))?;
+
+        utils::release_storage_image(builder, gradients_img);
+
+        // hysteresis
+        for i in 0..self.iterations {
+            let pipeline = Self::hysteresis_pipeline(ctx, i + 1 == self.iterations)?;
+
+            let set = PersistentDescriptorSet::new(
+                &ctx.memory.descriptor_set_allocator,
+                pipeline.layout().set_layouts().get(0).unwrap().clone(),
+                [
+                    WriteDescriptorSet::image_view(0, ImageView::new_default(images[0].clone())?),
+                    WriteDescriptorSet::image_view(1, ImageView::new_default(images[1].clone())?),
+                ],
+            )?;
+
+            builder
+                .bind_pipeline_compute(pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    pipeline.layout().clone(),
+                    0,
+                    set,
+                )
+                .dispatch(utils::workgroups(
+                    &input_img.dimensions().width_height(),
+                    &[TILE_SIZE, TILE_SIZE],
+                ))?;
+
+            images.swap(0, 1);
+        }
+
+        let [output_img, spare_img] = images;
+        utils::release_storage_image(builder, spare_img);
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("Canny", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+}
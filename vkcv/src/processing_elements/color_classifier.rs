@@ -0,0 +1,192 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{color_filter::Range, AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/color_classifier.comp.glsl",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+// limited by the size of the uniform block
+pub const MAX_CLASSES: usize = 16;
+
+// e.g. in TOML
+//
+//     [[elements.classes]]
+//     name = "red"
+//     range = { rgb_min = [0.9, 0.5, 0.5], rgb_max = [0.1, 1.0, 1.0] }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Class {
+    pub name: String,
+    pub range: Range,
+}
+
+impl Parameters for Vec<Class> {
+    fn encode(&self) -> Vec<u8> {
+        let mut params = cs::ty::Params {
+            rgb_min: [[0.0; 4]; MAX_CLASSES],
+            rgb_max: [[0.0; 4]; MAX_CLASSES],
+            invert: [[0; 4]; MAX_CLASSES],
+            count: self.len().min(MAX_CLASSES) as i32,
+        };
+
+        for (i, class) in self.iter().take(MAX_CLASSES).enumerate() {
+            let [r, g, b] = class.range.rgb_min;
+            params.rgb_min[i] = [r, g, b, 0.0];
+            let [r, g, b] = class.range.rgb_max;
+            params.rgb_max[i] = [r, g, b, 0.0];
+            let [r, g, b] = class.range.invert;
+            params.invert[i] = [r as i32, g as i32, b as i32, 0];
+        }
+
+        bytemuck::bytes_of(&params).to_vec()
+    }
+
+    fn validate(&self) -> Result<(), VkcvError> {
+        if self.is_empty() || self.len() > MAX_CLASSES {
+            return Err(VkcvError::InvalidParameters(format!(
+                "expected 1 to {} classes, got {}",
+                MAX_CLASSES,
+                self.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Assigns every pixel the label of the first class whose range contains it.
+//
+// The result is a r8 label image, 0 is the background and i the i-th class
+// (stored as i / 255), see `LabelTracker`.
+pub struct ColorClassifier {
+    classes: Param<Vec<Class>>,
+}
+
+impl ColorClassifier {
+    pub fn new(classes: Vec<Class>) -> Self {
+        Self {
+            classes: Param::new(classes),
+        }
+    }
+
+    // the names of the classes, label i is at index i - 1
+    pub fn names(&self) -> Vec<String> {
+        self.classes.get().into_iter().map(|c| c.name).collect()
+    }
+
+    // the ranges can be changed after the pipeline has been built, the number
+    // of classes has to stay the same
+    pub fn params(&self) -> Param<Vec<Class>> {
+        self.classes.clone()
+    }
+}
+
+impl ProcessingElement for ColorClassifier {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        let classes = self.classes.get();
+        classes.validate()?;
+
+        let pipeline = utils::compute_pipeline(ctx, cs::load, &cs::SpecializationConstants {})?;
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+
+        // output image
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo::from_image(&input_img, Format::R8_UNORM),
+        )?;
+
+        // setup layout
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        // the labels downstream refer to a fixed number of classes
+        let count = classes.len();
+        let classes_params = self.classes.bind_with(ctx, builder, move |classes| {
+            if classes.len() != count {
+                return Err(VkcvError::InvalidParameters(format!(
+                    "the pipeline was built for {} classes, got {}",
+                    count,
+                    classes.len()
+                )));
+            }
+            Ok(())
+        })?;
+
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, input_img_view),
+                WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::buffer(2, classes_params),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(
+                &input_img.dimensions().width_height(),
+                &[16, 16],
+            ))?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("ColorClassifier", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8G8B8A8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            format: Format::R8_UNORM,
+            ..info
+        })
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+}
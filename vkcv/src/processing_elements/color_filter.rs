@@ -1,11 +1,16 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
 use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
     format::Format,
     image::{view::ImageView, ImageAccess},
-    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    pipeline::{Pipeline, PipelineBindPoint},
 };
 
 use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
     utils::{self, ImageInfo},
     vk_init::VkContext,
 };
@@ -24,14 +29,103 @@ mod cs {
     }
 }
 
+// limited by the size of the uniform block
+pub const MAX_RANGES: usize = 8;
+
+// Selects pixels with min <= value <= max in every channel.
+//
+// A channel with min > max wraps around, e.g. hues from 0.9 to 0.1 select red.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Range {
+    pub rgb_min: [f32; 3],
+    pub rgb_max: [f32; 3],
+    // selects the values outside of the range of the channel instead
+    #[serde(default)]
+    pub invert: [bool; 3],
+}
+
+impl Range {
+    pub fn new(rgb_min: [f32; 3], rgb_max: [f32; 3]) -> Self {
+        Self {
+            rgb_min,
+            rgb_max,
+            invert: [false; 3],
+        }
+    }
+
+    // the same test as performed by the shader
+    pub fn contains(&self, rgb: [f32; 3]) -> bool {
+        (0..3).all(|c| {
+            let (lo, hi, v) = (self.rgb_min[c], self.rgb_max[c], rgb[c]);
+            let inside = if lo <= hi {
+                v >= lo && v <= hi
+            } else {
+                v >= lo || v <= hi
+            };
+            inside != self.invert[c]
+        })
+    }
+}
+
+// the union of up to `MAX_RANGES` ranges
+impl Parameters for Vec<Range> {
+    fn encode(&self) -> Vec<u8> {
+        let mut params = cs::ty::Params {
+            rgb_min: [[0.0; 4]; MAX_RANGES],
+            rgb_max: [[0.0; 4]; MAX_RANGES],
+            invert: [[0; 4]; MAX_RANGES],
+            count: self.len().min(MAX_RANGES) as i32,
+        };
+
+        for (i, range) in self.iter().take(MAX_RANGES).enumerate() {
+            let [r, g, b] = range.rgb_min;
+            params.rgb_min[i] = [r, g, b, 0.0];
+            let [r, g, b] = range.rgb_max;
+            params.rgb_max[i] = [r, g, b, 0.0];
+            let [r, g, b] = range.invert;
+            params.invert[i] = [r as i32, g as i32, b as i32, 0];
+        }
+
+        bytemuck::bytes_of(&params).to_vec()
+    }
+
+    fn validate(&self) -> Result<(), VkcvError> {
+        if self.is_empty() || self.len() > MAX_RANGES {
+            return Err(VkcvError::InvalidParameters(format!(
+                "expected 1 to {} ranges, got {}",
+                MAX_RANGES,
+                self.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
 pub struct ColorFilter {
-    rgb_min: [f32; 3],
-    rgb_max: [f32; 3],
+    params: Param<Vec<Range>>,
 }
 
 impl ColorFilter {
     pub fn new(rgb_min: [f32; 3], rgb_max: [f32; 3]) -> Self {
-        Self { rgb_min, rgb_max }
+        Self::with_ranges(vec![Range::new(rgb_min, rgb_max)])
+    }
+
+    // selects pixels inside of any of the ranges
+    pub fn with_ranges(ranges: Vec<Range>) -> Self {
+        Self {
+            params: Param::new(ranges),
+        }
+    }
+
+    // shares the ranges with other filters resp. with the caller
+    pub fn with_params(params: Param<Vec<Range>>) -> Self {
+        Self { params }
+    }
+
+    // the ranges can be changed after the pipeline has been built
+    pub fn params(&self) -> Param<Vec<Range>> {
+        self.params.clone()
     }
 }
 
@@ -41,31 +135,26 @@ impl ProcessingElement for ColorFilter {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
-        let pipeline = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs::SpecializationConstants {},
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+    ) -> Result<IoFragment, VkcvError> {
+        self.params.get().validate()?;
+
+        let pipeline = utils::compute_pipeline(ctx, cs::load, &cs::SpecializationConstants {})?;
 
         // input image
-        let input_img = input.output_image().unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
 
         // output image
-        let output_img =
-            utils::create_storage_image(ctx, &ImageInfo::from_image(&input_img, Format::R8_UNORM));
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo::from_image(&input_img, Format::R8_UNORM),
+        )?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
 
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = PersistentDescriptorSet::new(
             &ctx.memory.descriptor_set_allocator,
@@ -73,17 +162,11 @@ impl ProcessingElement for ColorFilter {
             [
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::buffer(2, self.params.bind(ctx, builder)?),
             ],
-        )
-        .unwrap();
+        )?;
 
         // build command buffer
-        let push_constants = cs::ty::PushConstants {
-            rgb_min: self.rgb_min,
-            rgb_max: self.rgb_max,
-            _dummy0: [0, 0, 0, 0],
-        };
-
         builder
             .bind_pipeline_compute(pipeline.clone())
             .bind_descriptor_sets(
@@ -92,17 +175,51 @@ impl ProcessingElement for ColorFilter {
                 0,
                 set,
             )
-            .push_constants(pipeline.layout().clone(), 0, push_constants)
             .dispatch(utils::workgroups(
                 &input_img.dimensions().width_height(),
                 &[16, 16],
-            ))
-            .unwrap();
+            ))?;
 
-        IoFragment {
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label: utils::basic_label("ColorFilter", &output_img),
-        }
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8G8B8A8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            format: Format::R8_UNORM,
+            ..info
+        })
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hue_wrap_around() {
+        let red = Range::new([0.9, 0.5, 0.5], [0.1, 1.0, 1.0]);
+        assert!(red.contains([0.95, 0.8, 0.8]));
+        assert!(red.contains([0.05, 0.8, 0.8]));
+        assert!(!red.contains([0.5, 0.8, 0.8]));
+        assert!(!red.contains([0.95, 0.2, 0.8]));
+
+        let not_green = Range {
+            invert: [true, false, false],
+            ..Range::new([0.3, 0.0, 0.0], [0.4, 1.0, 1.0])
+        };
+        assert!(not_green.contains([0.0, 0.5, 0.5]));
+        assert!(!not_green.contains([0.35, 0.5, 0.5]));
     }
 }
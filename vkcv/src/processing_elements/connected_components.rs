@@ -0,0 +1,566 @@
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    endpoints::image_download::TransferredImage,
+    error::VkcvError,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
+
+mod cs_cc {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/connected_components.comp.glsl",
+    }
+}
+
+mod cs_stats {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/component_stats.comp.glsl",
+    }
+}
+
+mod cs_stats_uint {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/component_stats.comp.glsl",
+        define: [("UINT_LABELS", "1")],
+    }
+}
+
+// rows of the table produced by `ComponentStats`
+//
+// The ids are handed out in the order in which the gpu visits the roots, i.e.
+// if there are more components, the ones which are missing from the table are
+// arbitrary, see `dropped`.
+pub const MAX_COMPONENTS: u32 = 255;
+
+// columns of the table produced by `ComponentStats`
+const TABLE_WIDTH: u32 = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Connectivity {
+    Four,
+    #[default]
+    Eight,
+}
+
+// Labels the connected foreground regions of a binary mask.
+//
+// The result is a r32ui label image, 0 is the background and i the i-th
+// component. The order of the components is arbitrary and can change from
+// frame to frame. Components smaller than `min_area` pixels are removed
+// before the ids are handed out, i.e. noise does not take up the rows of the
+// `ComponentStats` table.
+pub struct ConnectedComponents {
+    connectivity: Connectivity,
+    min_area: u32,
+}
+
+impl Default for ConnectedComponents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectedComponents {
+    pub fn new() -> Self {
+        Self::with_connectivity(Connectivity::Eight)
+    }
+
+    pub fn with_connectivity(connectivity: Connectivity) -> Self {
+        Self::with_min_area(connectivity, 0)
+    }
+
+    pub fn with_min_area(connectivity: Connectivity, min_area: u32) -> Self {
+        Self {
+            connectivity,
+            min_area,
+        }
+    }
+}
+
+impl ProcessingElement for ConnectedComponents {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        let local_size = 16;
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let info = ImageInfo::from(&input_img);
+
+        // parents resp. roots and the ids of the roots
+        let labels_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                format: Format::R32_UINT,
+                ..info
+            },
+        )?;
+        let ids_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                format: Format::R32_UINT,
+                ..info
+            },
+        )?;
+        let counter_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                width: 1,
+                height: 1,
+                format: Format::R32_UINT,
+            },
+        )?;
+
+        // output image
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                format: Format::R32_UINT,
+                ..info
+            },
+        )?;
+
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let labels_img_view = ImageView::new_default(labels_img.clone())?;
+        let ids_img_view = ImageView::new_default(ids_img.clone())?;
+        let counter_img_view = ImageView::new_default(counter_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        // init, merge, compress, count and relabel
+        for pass in 0..5 {
+            let pipeline = utils::compute_pipeline(
+                ctx,
+                cs_cc::load,
+                &cs_cc::SpecializationConstants {
+                    constant_0: local_size,
+                    constant_1: local_size,
+                    pass,
+                    eight_connected: (self.connectivity == Connectivity::Eight) as i32,
+                    min_area: self.min_area,
+                },
+            )?;
+
+            let set = PersistentDescriptorSet::new(
+                &ctx.memory.descriptor_set_allocator,
+                pipeline.layout().set_layouts().get(0).unwrap().clone(),
+                [
+                    WriteDescriptorSet::image_view(0, input_img_view.clone()),
+                    WriteDescriptorSet::image_view(1, labels_img_view.clone()),
+                    WriteDescriptorSet::image_view(2, ids_img_view.clone()),
+                    WriteDescriptorSet::image_view(3, counter_img_view.clone()),
+                    WriteDescriptorSet::image_view(4, output_img_view.clone()),
+                ],
+            )?;
+
+            builder
+                .bind_pipeline_compute(pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    pipeline.layout().clone(),
+                    0,
+                    set,
+                )
+                .dispatch(utils::workgroups(
+                    &input_img.dimensions().width_height(),
+                    &[local_size, local_size],
+                ))?;
+        }
+
+        utils::release_storage_image(builder, labels_img);
+        utils::release_storage_image(builder, ids_img);
+        utils::release_storage_image(builder, counter_img);
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("ConnectedComponents", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            format: Format::R32_UINT,
+            ..info
+        })
+    }
+}
+
+// Area, bounding box and centroid of every label of a label image, e.g. as
+// produced by `ConnectedComponents` or `ColorClassifier`.
+//
+// The result is a table (r32ui image) with one row per label, use `blobs` to
+// read it. Labels beyond `MAX_COMPONENTS` are missing from the table, but
+// counted, see `dropped`.
+pub struct ComponentStats {}
+
+impl Default for ComponentStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComponentStats {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ProcessingElement for ComponentStats {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        let local_size = 16;
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+
+        // output image
+        let table_info = ImageInfo {
+            width: TABLE_WIDTH,
+            height: MAX_COMPONENTS + 1,
+            format: Format::R32_UINT,
+        };
+        let output_img = utils::create_storage_image(ctx, builder, &table_info)?;
+
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        // clear the table, then accumulate
+        for (pass, dims) in [
+            (0, [table_info.width, table_info.height]),
+            (1, input_img.dimensions().width_height()),
+        ] {
+            let pipeline = if input_img.format() == Format::R32_UINT {
+                utils::compute_pipeline(
+                    ctx,
+                    cs_stats_uint::load,
+                    &cs_stats_uint::SpecializationConstants {
+                        constant_0: local_size,
+                        constant_1: local_size,
+                        pass,
+                    },
+                )?
+            } else {
+                utils::compute_pipeline(
+                    ctx,
+                    cs_stats::load,
+                    &cs_stats::SpecializationConstants {
+                        constant_0: local_size,
+                        constant_1: local_size,
+                        pass,
+                    },
+                )?
+            };
+
+            let set = PersistentDescriptorSet::new(
+                &ctx.memory.descriptor_set_allocator,
+                pipeline.layout().set_layouts().get(0).unwrap().clone(),
+                [
+                    WriteDescriptorSet::image_view(0, input_img_view.clone()),
+                    WriteDescriptorSet::image_view(1, output_img_view.clone()),
+                ],
+            )?;
+
+            builder
+                .bind_pipeline_compute(pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    pipeline.layout().clone(),
+                    0,
+                    set,
+                )
+                .dispatch(utils::workgroups(&dims, &[local_size, local_size]))?;
+        }
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("ComponentStats", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM, Format::R32_UINT]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|_| ImageInfo {
+            width: TABLE_WIDTH,
+            height: MAX_COMPONENTS + 1,
+            format: Format::R32_UINT,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blob {
+    // the value in the label image, starting at 1
+    pub label: u32,
+    // in pixels
+    pub area: u32,
+    // inclusive, in pixels
+    pub min: [u32; 2],
+    pub max: [u32; 2],
+    pub centroid: [f32; 2],
+    // second-order central moments normalized by the area, i.e. the
+    // (co)variances of the pixel coordinates
+    pub mu20: f32,
+    pub mu02: f32,
+    pub mu11: f32,
+    // angle of the major axis to the x axis in radians
+    pub orientation: f32,
+    // full lengths of the axes of the ellipse with the same moments
+    pub major_axis: f32,
+    pub minor_axis: f32,
+    // see `add_mean_depth`
+    pub mean_depth: Option<f32>,
+}
+
+impl Blob {
+    // sums of x, y, x^2, y^2 and xy
+    fn from_sums(label: u32, area: u32, min: [u32; 2], max: [u32; 2], sums: [u64; 5]) -> Self {
+        let a = area as f64;
+        let [x, y, xx, yy, xy] = sums.map(|s| s as f64 / a);
+
+        let mu20 = xx - x * x;
+        let mu02 = yy - y * y;
+        let mu11 = xy - x * y;
+
+        // eigenvalues of the covariance matrix
+        let mean = (mu20 + mu02) / 2.0;
+        let delta = ((mu20 - mu02).powi(2) / 4.0 + mu11 * mu11).sqrt();
+        let (l1, l2) = (mean + delta, (mean - delta).max(0.0));
+
+        Self {
+            label,
+            area,
+            min,
+            max,
+            centroid: [x as f32, y as f32],
+            mu20: mu20 as f32,
+            mu02: mu02 as f32,
+            mu11: mu11 as f32,
+            orientation: (0.5 * (2.0 * mu11).atan2(mu20 - mu02)) as f32,
+            major_axis: (4.0 * l1.sqrt()) as f32,
+            minor_axis: (4.0 * l2.sqrt()) as f32,
+            mean_depth: None,
+        }
+    }
+}
+
+fn table_buffer(tf_img: &TransferredImage) -> Result<&[u8], VkcvError> {
+    let info = tf_img.info();
+    if info.format != Format::R32_UINT {
+        return Err(VkcvError::UnsupportedFormat(info.format));
+    }
+    if info.width != TABLE_WIDTH || info.height != MAX_COMPONENTS + 1 {
+        return Err(VkcvError::InvalidParameters(format!(
+            "expected a {}x{} table, got {}x{}",
+            TABLE_WIDTH,
+            MAX_COMPONENTS + 1,
+            info.width,
+            info.height
+        )));
+    }
+
+    Ok(tf_img.buffer_content())
+}
+
+// the blobs found in the table produced by `ComponentStats`, the largest first
+pub fn blobs(tf_img: &TransferredImage) -> Result<Vec<Blob>, VkcvError> {
+    Ok(parse_table(table_buffer(tf_img)?))
+}
+
+// the number of components missing from the table produced by
+// `ComponentStats`, i.e. the labels beyond `MAX_COMPONENTS`
+pub fn dropped(tf_img: &TransferredImage) -> Result<u32, VkcvError> {
+    Ok(parse_dropped(table_buffer(tf_img)?))
+}
+
+fn parse_dropped(buffer: &[u8]) -> u32 {
+    let o = (MAX_COMPONENTS * TABLE_WIDTH * 4) as usize;
+    let highest = u32::from_le_bytes([buffer[o], buffer[o + 1], buffer[o + 2], buffer[o + 3]]);
+    highest.saturating_sub(MAX_COMPONENTS)
+}
+
+fn parse_table(buffer: &[u8]) -> Vec<Blob> {
+    let mut blobs: Vec<_> = buffer
+        .chunks_exact(TABLE_WIDTH as usize * 4)
+        .take(MAX_COMPONENTS as usize)
+        .enumerate()
+        .filter_map(|(i, row)| {
+            let v = |c: usize| {
+                u32::from_le_bytes([row[4 * c], row[4 * c + 1], row[4 * c + 2], row[4 * c + 3]])
+            };
+            let v64 = |c: usize| v(c) as u64 | (v(c + 1) as u64) << 32;
+            let area = v(0);
+
+            (area > 0).then(|| {
+                Blob::from_sums(
+                    i as u32 + 1,
+                    area,
+                    [v(1), v(2)],
+                    [v(3), v(4)],
+                    [v64(5), v64(7), v64(9), v64(11), v64(13)],
+                )
+            })
+        })
+        .collect();
+
+    blobs.sort_by(|a, b| b.area.cmp(&a.area));
+    blobs
+}
+
+// Sets the mean depth of every blob, sampling every `step`-th pixel of the
+// label image (resp. the image `ComponentStats` was connected to).
+//
+// `depth` returns the depth at a pixel, e.g. using `Realsense::depth_at_pixel`,
+// pixels without a valid depth are ignored.
+pub fn add_mean_depth<F>(
+    blobs: &mut [Blob],
+    labels: &TransferredImage,
+    step: u32,
+    mut depth: F,
+) -> Result<(), VkcvError>
+where
+    F: FnMut([f32; 2]) -> Option<f32>,
+{
+    let info = labels.info();
+    if !matches!(info.format, Format::R8_UNORM | Format::R32_UINT) {
+        return Err(VkcvError::UnsupportedFormat(info.format));
+    }
+
+    // e.g. produced by `ColorClassifier` resp. `ConnectedComponents`
+    let buffer = labels.buffer_content();
+    let label_at = |i: usize| match info.format {
+        Format::R32_UINT => u32::from_le_bytes([
+            buffer[4 * i],
+            buffer[4 * i + 1],
+            buffer[4 * i + 2],
+            buffer[4 * i + 3],
+        ]),
+        _ => buffer[i] as u32,
+    };
+
+    let width = info.width as usize;
+    let step = step.max(1) as usize;
+
+    for blob in blobs.iter_mut() {
+        // e.g. blobs of a table computed from another image
+        if blob.max[0] >= info.width || blob.max[1] >= info.height {
+            return Err(VkcvError::InvalidParameters(format!(
+                "blob {} exceeds the {}x{} label image",
+                blob.label, info.width, info.height
+            )));
+        }
+
+        let (mut sum, mut count) = (0.0, 0);
+
+        for y in (blob.min[1] as usize..=blob.max[1] as usize).step_by(step) {
+            for x in (blob.min[0] as usize..=blob.max[0] as usize).step_by(step) {
+                if label_at(y * width + x) != blob.label {
+                    continue;
+                }
+                if let Some(d) = depth([x as f32, y as f32]) {
+                    sum += d;
+                    count += 1;
+                }
+            }
+        }
+
+        blob.mean_depth = (count > 0).then(|| sum / count as f32);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(area: u32, min: [u32; 2], max: [u32; 2], sums: [u64; 5]) -> Vec<u8> {
+        let mut values = vec![area, min[0], min[1], max[0], max[1]];
+        for s in sums {
+            values.extend([s as u32, (s >> 32) as u32]);
+        }
+        values.push(0);
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn table() {
+        let mut buffer = vec![];
+        // horizontal line (0, 0) .. (2, 0)
+        buffer.extend(row(3, [0, 0], [2, 0], [3, 0, 5, 0, 0]));
+        // not found
+        buffer.extend(row(0, [u32::MAX; 2], [0, 0], [0; 5]));
+        // 2x2 square at (10, 20), the sums of x^2 exceed 32bit
+        let big = 1u64 << 33;
+        buffer.extend(row(
+            4,
+            [10, 20],
+            [11, 21],
+            [42, 82, 442 + 4 * big, 1682, 861],
+        ));
+
+        let blobs = parse_table(&buffer);
+        assert_eq!(blobs.len(), 2);
+
+        // sorted by area
+        assert_eq!(blobs[0].label, 3);
+        assert_eq!(blobs[0].centroid, [10.5, 20.5]);
+        assert_eq!(blobs[0].max, [11, 21]);
+        assert!(blobs[0].mu20 > 1e9);
+
+        let line = blobs[1];
+        assert_eq!(line.centroid, [1.0, 0.0]);
+        assert!((line.mu20 - 2.0 / 3.0).abs() < 1e-6);
+        assert_eq!(line.orientation, 0.0);
+        assert!(line.minor_axis < 1e-3);
+        assert!(line.major_axis > line.minor_axis);
+    }
+
+    #[test]
+    fn dropped() {
+        let mut buffer = vec![];
+        for _ in 0..MAX_COMPONENTS {
+            buffer.extend(row(1, [0, 0], [0, 0], [0; 5]));
+        }
+        // the highest label, i.e. 45 labels are missing from the table
+        buffer.extend(row(MAX_COMPONENTS + 45, [0, 0], [0, 0], [0; 5]));
+
+        assert_eq!(parse_table(&buffer).len(), MAX_COMPONENTS as usize);
+        assert_eq!(parse_dropped(&buffer), 45);
+
+        let last = buffer.len() - TABLE_WIDTH as usize * 4;
+        buffer[last..last + 4].copy_from_slice(&12u32.to_le_bytes());
+        assert_eq!(parse_dropped(&buffer), 0);
+    }
+}
@@ -1,63 +1,258 @@
+use std::{any::Any, sync::Arc};
+
+use serde::{Deserialize, Serialize};
 use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
-    image::{view::ImageView, ImageAccess},
+    format::Format,
+    image::{view::ImageView, ImageAccess, StorageImage},
     pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
 };
 
-use crate::{utils, vk_init::VkContext};
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
 
 use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
 
-mod cs {
-    vulkano_shaders::shader! {
-        ty: "compute",
-        path: "src/shaders/convolution3x3.comp.glsl",
+// all shaders share the same uniform block
+macro_rules! convolution_shader {
+    ($name:ident, $path:tt, [$($define:tt),*]) => {
+        mod $name {
+            vulkano_shaders::shader! {
+                ty: "compute",
+                path: $path,
+                define: [$($define),*],
+                types_meta: {
+                    use bytemuck::{Pod, Zeroable};
+
+                    #[derive(Clone, Copy, Zeroable, Pod)]
+                },
+            }
+        }
+    };
+}
+
+convolution_shader!(
+    cs_r8,
+    "src/shaders/convolution.comp.glsl",
+    [("FORMAT", "r8")]
+);
+convolution_shader!(
+    cs_rgba8,
+    "src/shaders/convolution.comp.glsl",
+    [("FORMAT", "rgba8")]
+);
+convolution_shader!(
+    sep1_r8,
+    "src/shaders/convolution_sep.comp.glsl",
+    [("FORMAT", "r8"), ("FIRST_PASS", "1")]
+);
+convolution_shader!(
+    sep2_r8,
+    "src/shaders/convolution_sep.comp.glsl",
+    [("FORMAT", "r8")]
+);
+convolution_shader!(
+    sep1_rgba8,
+    "src/shaders/convolution_sep.comp.glsl",
+    [("FORMAT", "rgba8"), ("FIRST_PASS", "1")]
+);
+convolution_shader!(
+    sep2_rgba8,
+    "src/shaders/convolution_sep.comp.glsl",
+    [("FORMAT", "rgba8")]
+);
+
+const FORMATS: [Format; 2] = [Format::R8_UNORM, Format::R8G8B8A8_UNORM];
+
+// limited by the size of the uniform block
+pub const MAX_SIZE: usize = 15;
+
+// how pixels outside of the image are sampled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Border {
+    // repeats the edge pixel
+    #[default]
+    Clamp,
+    // reflects at the edge pixel, i.e. without repeating it
+    Mirror,
+    // treats outside pixels as 0
+    Zero,
+    Wrap,
+}
+
+// result = clamp(sum(weights * neighborhood) / denom + offset, 0, 1)
+//
+// The kernel must have an odd width and height of at most `MAX_SIZE`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Kernel {
+    // row major, i.e. weights[y][x]
+    pub weights: Vec<Vec<f32>>,
+    pub offset: f32,
+    pub denom: f32,
+    pub border: Border,
+}
+
+impl Default for Kernel {
+    fn default() -> Self {
+        Self {
+            weights: vec![
+                vec![-1.0, 0.0, 0.0],
+                vec![0.0, -1.0, 0.0],
+                vec![0.0, 0.0, 2.0],
+            ],
+            offset: 0.5,
+            denom: 2.0,
+            border: Border::Clamp,
+        }
     }
 }
 
-pub struct Convolution {}
+impl Kernel {
+    pub fn width(&self) -> usize {
+        self.weights.first().map_or(0, |row| row.len())
+    }
+
+    pub fn height(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn validate(&self) -> Result<(), VkcvError> {
+        let (width, height) = (self.width(), self.height());
+
+        if width % 2 == 0 || height % 2 == 0 || width > MAX_SIZE || height > MAX_SIZE {
+            return Err(VkcvError::InvalidParameters(format!(
+                "kernel size {}x{} must be odd and at most {}x{}",
+                width, height, MAX_SIZE, MAX_SIZE
+            )));
+        }
+
+        if self.weights.iter().any(|row| row.len() != width) {
+            return Err(VkcvError::InvalidParameters(
+                "all rows of the kernel must have the same length".to_string(),
+            ));
+        }
+
+        if self.denom == 0.0 {
+            return Err(VkcvError::InvalidParameters(
+                "denom must not be 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // the row and column vector if the kernel is their outer product,
+    // i.e. weights[y][x] = column[y] * row[x]
+    pub fn factors(&self) -> Option<(Vec<f32>, Vec<f32>)> {
+        if self.weights.iter().any(|row| row.len() != self.width()) {
+            return None;
+        }
+
+        // pivot on the largest weight for numerical stability
+        let (r0, c0, pivot) = self
+            .weights
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, w)| (y, x, *w)))
+            .max_by(|a, b| a.2.abs().total_cmp(&b.2.abs()))?;
+
+        if pivot == 0.0 {
+            return None;
+        }
+
+        let row = self.weights[r0].clone();
+        let column: Vec<f32> = self.weights.iter().map(|r| r[c0] / pivot).collect();
+
+        let tolerance = 1e-5 * pivot.abs();
+        let separable = self.weights.iter().zip(&column).all(|(r, c)| {
+            r.iter()
+                .zip(&row)
+                .all(|(w, x)| (w - c * x).abs() <= tolerance)
+        });
+
+        separable.then_some((row, column))
+    }
+}
+
+fn pack<const N: usize>(values: impl Iterator<Item = f32>) -> [[f32; 4]; N] {
+    let mut packed = [[0.0; 4]; N];
+    for (i, v) in values.take(4 * N).enumerate() {
+        packed[i / 4][i % 4] = v;
+    }
+    packed
+}
+
+impl Parameters for Kernel {
+    fn encode(&self) -> Vec<u8> {
+        let (row, column) = self.factors().unwrap_or_default();
+
+        bytemuck::bytes_of(&cs_r8::ty::Params {
+            weights: pack(self.weights.iter().flatten().copied()),
+            row: pack(row.into_iter()),
+            column: pack(column.into_iter()),
+            width: self.width() as i32,
+            height: self.height() as i32,
+            offset: self.offset,
+            denom: self.denom,
+            border: self.border as i32,
+        })
+        .to_vec()
+    }
+
+    fn validate(&self) -> Result<(), VkcvError> {
+        Kernel::validate(self)
+    }
+}
+
+// first and second pass of a separable convolution
+type Pipelines = (Arc<ComputePipeline>, Arc<ComputePipeline>);
+
+// Convolution with an arbitrary kernel.
+//
+// Rank-1 kernels are applied in two passes (rows, then columns) using a
+// rgba16f intermediate image, everything else in a single pass.
+pub struct Convolution {
+    kernel: Param<Kernel>,
+}
 
 impl Convolution {
     pub fn new() -> Self {
-        Self {}
+        Self::with_kernel(Kernel::default())
     }
-}
 
-impl ProcessingElement for Convolution {
-    fn build(
+    pub fn with_kernel(kernel: Kernel) -> Self {
+        Self {
+            kernel: Param::new(kernel),
+        }
+    }
+
+    // The kernel can be changed after the pipeline has been built, as long as
+    // a separable kernel stays separable.
+    pub fn params(&self) -> Param<Kernel> {
+        self.kernel.clone()
+    }
+
+    fn single_pass(
         &self,
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
-        input: &IoFragment,
-    ) -> IoFragment {
-        let local_size = 16;
-
-        let pipeline = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs::SpecializationConstants {
-                    constant_0: local_size,
-                    constant_1: local_size,
-                    ..Default::default()
-                },
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
-
-        // input image
-        let input_img = input.output_image().unwrap();
-
+        pipeline: Arc<ComputePipeline>,
+        input_img: Arc<StorageImage>,
+        local_size: u32,
+    ) -> Result<IoFragment, VkcvError> {
         // output image
-        let output_img = utils::create_storage_image(ctx, &(&input_img).into());
+        let output_img = utils::create_storage_image(ctx, builder, &(&input_img).into())?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = PersistentDescriptorSet::new(
             &ctx.memory.descriptor_set_allocator,
@@ -65,15 +260,9 @@ impl ProcessingElement for Convolution {
             [
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::buffer(2, self.kernel.bind(ctx, builder)?),
             ],
-        )
-        .unwrap();
-
-        // let push_constants = cs::ty::PushConstants {
-        //     kernel: [-1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 2.0],
-        //     offset: 1.0,
-        //     denom: 0.5,
-        // };
+        )?;
 
         // build command buffer
         builder
@@ -84,17 +273,213 @@ impl ProcessingElement for Convolution {
                 0,
                 set,
             )
-            // .push_constants(pipeline.layout().clone(), 0, push_constants)
             .dispatch(utils::workgroups(
                 &input_img.dimensions().width_height(),
                 &[local_size, local_size],
-            ))
-            .unwrap();
+            ))?;
 
-        IoFragment {
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label: utils::basic_label("Convolution single pass", &output_img),
+        })
+    }
+
+    fn two_passes(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        (pipeline_1p, pipeline_2p): Pipelines,
+        input_img: Arc<StorageImage>,
+        local_size: u32,
+    ) -> Result<IoFragment, VkcvError> {
+        let info = ImageInfo::from(&input_img);
+
+        // the first pass writes the transposed image
+        let intermediate_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                width: info.height,
+                height: info.width,
+                format: Format::R16G16B16A16_SFLOAT,
+            },
+        )?;
+        let output_img = utils::create_storage_image(ctx, builder, &info)?;
+
+        // setup layout
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let intermediate_img_view = ImageView::new_default(intermediate_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+        // the passes cannot apply a kernel which is not separable
+        let params = self
+            .kernel
+            .bind_with(ctx, builder, |kernel| match kernel.factors() {
+                Some(_) => Ok(()),
+                None => Err(VkcvError::InvalidParameters(
+                    "the pipeline was built for a separable kernel".to_string(),
+                )),
+            })?;
+
+        let set_1p = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            pipeline_1p.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::image_view(0, input_img_view),
+                WriteDescriptorSet::image_view(1, intermediate_img_view.clone()),
+                WriteDescriptorSet::buffer(2, params.clone()),
+            ],
+        )?;
+
+        let set_2p = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            pipeline_2p.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::image_view(0, intermediate_img_view),
+                WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::buffer(2, params),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(pipeline_1p.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline_1p.layout().clone(),
+                0,
+                set_1p,
+            )
+            .dispatch(utils::workgroups(
+                &input_img.dimensions().width_height(),
+                &[local_size, local_size],
+            ))?
+            .bind_pipeline_compute(pipeline_2p.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline_2p.layout().clone(),
+                0,
+                set_2p,
+            )
+            .dispatch(utils::workgroups(
+                &intermediate_img.dimensions().width_height(),
+                &[local_size, local_size],
+            ))?;
+
+        utils::release_storage_image(builder, intermediate_img);
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("Convolution two passes", &output_img),
+        })
+    }
+}
+
+impl ProcessingElement for Convolution {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        let local_size = 16;
+
+        let kernel = self.kernel.get();
+        Kernel::validate(&kernel)?;
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+
+        macro_rules! pipeline {
+            ($cs:ident) => {
+                utils::compute_pipeline(
+                    ctx,
+                    $cs::load,
+                    &$cs::SpecializationConstants {
+                        constant_0: local_size,
+                        constant_1: local_size,
+                    },
+                )?
+            };
         }
+
+        let format = input_img.format();
+        if kernel.factors().is_some() {
+            let pipelines = match format {
+                Format::R8_UNORM => (pipeline!(sep1_r8), pipeline!(sep2_r8)),
+                Format::R8G8B8A8_UNORM => (pipeline!(sep1_rgba8), pipeline!(sep2_rgba8)),
+                format => return Err(VkcvError::UnsupportedFormat(format)),
+            };
+            self.two_passes(ctx, builder, pipelines, input_img, local_size)
+        } else {
+            let pipeline = match format {
+                Format::R8_UNORM => pipeline!(cs_r8),
+                Format::R8G8B8A8_UNORM => pipeline!(cs_rgba8),
+                format => return Err(VkcvError::UnsupportedFormat(format)),
+            };
+            self.single_pass(ctx, builder, pipeline, input_img, local_size)
+        }
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &FORMATS
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn separable_kernels() {
+        let box_blur = Kernel {
+            weights: vec![vec![1.0; 5]; 3],
+            denom: 15.0,
+            ..Default::default()
+        };
+        assert!(box_blur.validate().is_ok());
+        let (row, column) = box_blur.factors().unwrap();
+        assert_eq!(row, [1.0; 5]);
+        assert_eq!(column, [1.0; 3]);
+
+        let sobel_x = Kernel {
+            weights: vec![
+                vec![-1.0, 0.0, 1.0],
+                vec![-2.0, 0.0, 2.0],
+                vec![-1.0, 0.0, 1.0],
+            ],
+            ..Default::default()
+        };
+        let (row, column) = sobel_x.factors().unwrap();
+        assert_eq!(row, [-2.0, 0.0, 2.0]);
+        assert_eq!(column, [0.5, 1.0, 0.5]);
+
+        assert!(Kernel::default().factors().is_none());
+    }
+
+    #[test]
+    fn invalid_kernels() {
+        let even = Kernel {
+            weights: vec![vec![1.0; 4]; 3],
+            ..Default::default()
+        };
+        assert!(even.validate().is_err());
+
+        let ragged = Kernel {
+            weights: vec![vec![1.0; 3], vec![1.0; 3], vec![1.0; 1]],
+            ..Default::default()
+        };
+        assert!(ragged.validate().is_err());
+
+        let large = Kernel {
+            weights: vec![vec![1.0; MAX_SIZE + 2]; 1],
+            ..Default::default()
+        };
+        assert!(large.validate().is_err());
     }
 }
@@ -1,10 +1,11 @@
 use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
     image::{view::ImageView, ImageAccess},
-    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    pipeline::{Pipeline, PipelineBindPoint},
 };
 
-use crate::{utils, vk_init::VkContext};
+use crate::{error::VkcvError, utils, vk_init::VkContext};
 
 use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
 
@@ -29,64 +30,52 @@ impl ProcessingElement for Convolution2Pass {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
+    ) -> Result<IoFragment, VkcvError> {
         let local_size = 32;
 
         // shader for the first pass
-        let pipeline_1p = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs::SpecializationConstants {
-                    constant_0: local_size,
-                    constant_1: local_size,
-                    offset: 0.5,
-                    m1: 1.0,
-                    m2: 2.0,
-                    m3: 1.0,
-                    ..Default::default()
-                },
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+        let pipeline_1p = utils::compute_pipeline(
+            ctx,
+            cs::load,
+            &cs::SpecializationConstants {
+                constant_0: local_size,
+                constant_1: local_size,
+                offset: 0.5,
+                m1: 1.0,
+                m2: 2.0,
+                m3: 1.0,
+                ..Default::default()
+            },
+        )?;
 
         // input image
-        let input_img = input.output_image().unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
 
         // shader for the second pass
-        let pipeline_2p = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs::SpecializationConstants {
-                    constant_0: local_size,
-                    constant_1: local_size,
-                    v_pass: 1,
-                    offset: 0.5,
-                    m4: 1.0,
-                    m5: 0.0,
-                    m6: -1.0,
-                    ..Default::default()
-                },
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+        let pipeline_2p = utils::compute_pipeline(
+            ctx,
+            cs::load,
+            &cs::SpecializationConstants {
+                constant_0: local_size,
+                constant_1: local_size,
+                v_pass: 1,
+                offset: 0.5,
+                m4: 1.0,
+                m5: 0.0,
+                m6: -1.0,
+                ..Default::default()
+            },
+        )?;
 
         // output image for first pass
-        let intermediate_img = utils::create_storage_image(ctx, &(&input_img).into());
+        let intermediate_img = utils::create_storage_image(ctx, builder, &(&input_img).into())?;
         // output image for second pass
-        let output_img = utils::create_storage_image(ctx, &(&input_img).into());
+        let output_img = utils::create_storage_image(ctx, builder, &(&input_img).into())?;
 
         // setup layout
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let intermediate_img_view = ImageView::new_default(intermediate_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let intermediate_img_view = ImageView::new_default(intermediate_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let layout_1p = pipeline_1p.layout().set_layouts().get(0).unwrap();
 
@@ -97,8 +86,7 @@ impl ProcessingElement for Convolution2Pass {
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, intermediate_img_view.clone()),
             ],
-        )
-        .unwrap();
+        )?;
 
         let layout_2p = pipeline_2p.layout().set_layouts().get(0).unwrap();
 
@@ -109,8 +97,7 @@ impl ProcessingElement for Convolution2Pass {
                 WriteDescriptorSet::image_view(0, intermediate_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
             ],
-        )
-        .unwrap();
+        )?;
 
         // build command buffer
         builder
@@ -125,8 +112,7 @@ impl ProcessingElement for Convolution2Pass {
             .dispatch(utils::workgroups(
                 &input_img.dimensions().width_height(),
                 &[local_size, local_size],
-            ))
-            .unwrap()
+            ))?
             .bind_pipeline_compute(pipeline_2p.clone())
             .bind_descriptor_sets(
                 PipelineBindPoint::Compute,
@@ -138,13 +124,18 @@ impl ProcessingElement for Convolution2Pass {
             .dispatch(utils::workgroups(
                 &input_img.dimensions().width_height(),
                 &[local_size, local_size],
-            ))
-            .unwrap();
+            ))?;
+
+        utils::release_storage_image(builder, intermediate_img);
 
-        IoFragment {
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label: utils::basic_label("Convolution two passes", &output_img),
-        }
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
     }
 }
@@ -0,0 +1,191 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::{ClearColorValue, Format},
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    state_image::SharedState,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
+
+mod cs_r8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/frame_difference.comp.glsl",
+        define: [("FORMAT", "r8")],
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod cs_rgba8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/frame_difference.comp.glsl",
+        define: [("FORMAT", "rgba8")],
+    }
+}
+
+const FORMATS: [Format; 2] = [Format::R8_UNORM, Format::R8G8B8A8_UNORM];
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // the output is a binary mask if > 0, otherwise the difference itself
+    pub threshold: f32,
+}
+
+impl Parameters for Settings {
+    fn encode(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&cs_r8::ty::Params {
+            threshold: self.threshold,
+        })
+        .to_vec()
+    }
+}
+
+// Absolute difference between the current and the previous frame, the
+// largest over all channels. The previous frame is kept in a state image
+// shared by all pipelines the element is built into, the first frame (also
+// after a `reset`) yields no difference.
+//
+// The r8 output can be combined with e.g. the mask of a color filter through
+// a `BinaryOp` in a pipeline graph.
+pub struct FrameDifference {
+    settings: Param<Settings>,
+    state: SharedState,
+}
+
+impl Default for FrameDifference {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDifference {
+    pub fn new() -> Self {
+        Self::with_settings(Settings::default())
+    }
+
+    pub fn with_threshold(threshold: f32) -> Self {
+        Self::with_settings(Settings { threshold })
+    }
+
+    pub fn with_settings(settings: Settings) -> Self {
+        Self {
+            settings: Param::new(settings),
+            state: SharedState::new(),
+        }
+    }
+
+    // the threshold can be changed after the pipeline has been built
+    pub fn params(&self) -> Param<Settings> {
+        self.settings.clone()
+    }
+}
+
+impl ProcessingElement for FrameDifference {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let format = input_img.format();
+
+        let pipeline = match format {
+            Format::R8_UNORM => {
+                utils::compute_pipeline(ctx, cs_r8::load, &cs_r8::SpecializationConstants {})?
+            }
+            Format::R8G8B8A8_UNORM => {
+                utils::compute_pipeline(ctx, cs_rgba8::load, &cs_rgba8::SpecializationConstants {})?
+            }
+            _ => return Err(VkcvError::UnsupportedFormat(format)),
+        };
+
+        // output image
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo::from_image(&input_img, Format::R8_UNORM),
+        )?;
+
+        // an alpha of 0 marks pixels which have not been seen yet
+        let previous_img = self.state.get_or_create(
+            ctx,
+            &ImageInfo::from_image(&input_img, Format::R8G8B8A8_UNORM),
+            ClearColorValue::Float([0.0; 4]),
+        )?;
+
+        // setup layout
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, input_img_view),
+                WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::image_view(2, previous_img.view()?),
+                WriteDescriptorSet::buffer(3, self.settings.bind(ctx, builder)?),
+                WriteDescriptorSet::buffer(4, previous_img.bind_reset(ctx, builder)?),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(
+                &input_img.dimensions().width_height(),
+                &[16, 16],
+            ))?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("FrameDifference", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &FORMATS
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            format: Format::R8_UNORM,
+            ..info
+        })
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+
+    fn reset(&self) {
+        self.state.reset();
+    }
+}
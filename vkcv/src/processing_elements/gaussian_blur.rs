@@ -0,0 +1,286 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
+
+mod cs_r8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/gaussian_blur.comp.glsl",
+        define: [("FORMAT", "r8")],
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod cs_rgba8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/gaussian_blur.comp.glsl",
+        define: [("FORMAT", "rgba8")],
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+const FORMATS: [Format; 2] = [Format::R8_UNORM, Format::R8G8B8A8_UNORM];
+
+// limited by the size of the uniform block
+pub const MAX_RADIUS: u32 = 31;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Blur {
+    pub sigma: f32,
+    // defaults to 3 sigma, clamped to `MAX_RADIUS`
+    #[serde(default)]
+    pub radius: Option<u32>,
+}
+
+impl Blur {
+    pub fn radius(&self) -> u32 {
+        let radius = match self.radius {
+            Some(radius) => radius,
+            None => (3.0 * self.sigma).ceil().max(0.0) as u32,
+        };
+        radius.min(MAX_RADIUS)
+    }
+
+    // one side of the normalized kernel, starting at the center
+    pub fn weights(&self) -> Vec<f32> {
+        if self.sigma <= 0.0 {
+            return vec![1.0];
+        }
+
+        let weights: Vec<f32> = (0..=self.radius())
+            .map(|i| (-((i * i) as f32) / (2.0 * self.sigma * self.sigma)).exp())
+            .collect();
+        let sum = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+
+        weights.iter().map(|w| w / sum).collect()
+    }
+}
+
+impl Parameters for Blur {
+    fn encode(&self) -> Vec<u8> {
+        let weights = self.weights();
+        let mut packed = [[0.0; 4]; 8];
+        for (i, w) in weights.iter().enumerate() {
+            packed[i / 4][i % 4] = *w;
+        }
+
+        // both shaders share the same uniform block
+        bytemuck::bytes_of(&cs_r8::ty::Params {
+            weights: packed,
+            radius: weights.len() as i32 - 1,
+        })
+        .to_vec()
+    }
+
+    fn validate(&self) -> Result<(), VkcvError> {
+        // also rejects NaN
+        if !(self.sigma > 0.0 && self.sigma.is_finite()) {
+            return Err(VkcvError::InvalidParameters(format!(
+                "sigma must be positive, got {}",
+                self.sigma
+            )));
+        }
+        Ok(())
+    }
+}
+
+// separable gaussian blur in two passes, each blurring along x and storing the
+// result transposed
+pub struct GaussianBlur {
+    blur: Param<Blur>,
+}
+
+impl GaussianBlur {
+    pub fn new(sigma: f32) -> Self {
+        Self::with_radius(sigma, None)
+    }
+
+    pub fn with_radius(sigma: f32, radius: Option<u32>) -> Self {
+        Self {
+            blur: Param::new(Blur { sigma, radius }),
+        }
+    }
+
+    // sigma and radius can be changed after the pipeline has been built
+    pub fn params(&self) -> Param<Blur> {
+        self.blur.clone()
+    }
+}
+
+impl ProcessingElement for GaussianBlur {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        let local_size = 16;
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let info = ImageInfo::from(&input_img);
+
+        let pipeline = match info.format {
+            Format::R8_UNORM => utils::compute_pipeline(
+                ctx,
+                cs_r8::load,
+                &cs_r8::SpecializationConstants {
+                    constant_0: local_size,
+                    constant_1: local_size,
+                },
+            )?,
+            Format::R8G8B8A8_UNORM => utils::compute_pipeline(
+                ctx,
+                cs_rgba8::load,
+                &cs_rgba8::SpecializationConstants {
+                    constant_0: local_size,
+                    constant_1: local_size,
+                },
+            )?,
+            format => return Err(VkcvError::UnsupportedFormat(format)),
+        };
+
+        // the first pass writes the transposed image
+        let intermediate_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                width: info.height,
+                height: info.width,
+                format: info.format,
+            },
+        )?;
+        let output_img = utils::create_storage_image(ctx, builder, &info)?;
+
+        // setup layout
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let intermediate_img_view = ImageView::new_default(intermediate_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+        let params = self.blur.bind(ctx, builder)?;
+
+        let set_1p = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, input_img_view),
+                WriteDescriptorSet::image_view(1, intermediate_img_view.clone()),
+                WriteDescriptorSet::buffer(2, params.clone()),
+            ],
+        )?;
+
+        let set_2p = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, intermediate_img_view),
+                WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::buffer(2, params),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set_1p,
+            )
+            .dispatch(utils::workgroups(
+                &input_img.dimensions().width_height(),
+                &[local_size, local_size],
+            ))?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set_2p,
+            )
+            .dispatch(utils::workgroups(
+                &intermediate_img.dimensions().width_height(),
+                &[local_size, local_size],
+            ))?;
+
+        utils::release_storage_image(builder, intermediate_img);
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("GaussianBlur", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &FORMATS
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalized_weights() {
+        let blur = Blur {
+            sigma: 1.5,
+            radius: None,
+        };
+        let weights = blur.weights();
+
+        assert_eq!(weights.len(), 6);
+        assert!(weights.windows(2).all(|w| w[0] > w[1]));
+
+        let sum = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+        assert!((sum - 1.0).abs() < 1e-6);
+
+        let blur = Blur {
+            sigma: 100.0,
+            radius: None,
+        };
+        assert_eq!(blur.radius(), MAX_RADIUS);
+        assert_eq!(blur.weights().len(), MAX_RADIUS as usize + 1);
+    }
+
+    #[test]
+    fn invalid_sigma() {
+        for sigma in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let blur = Blur {
+                sigma,
+                radius: None,
+            };
+            assert!(blur.validate().is_err());
+            assert!(GaussianBlur::new(1.0).params().set(blur).is_err());
+        }
+    }
+}
@@ -0,0 +1,719 @@
+use std::{fmt, sync::Arc};
+
+use vulkano::format::Format;
+
+use crate::{
+    error::VkcvError,
+    image_pool::{self, ImagePool, MemoryReport},
+    profiler::Profiler,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{
+    AutoCommandBufferBuilder, IoFragment, MultiInputElement, ProcessingElement, Recording,
+};
+
+// default port names
+pub const IN: &str = "in";
+pub const OUT: &str = "out";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+enum NodeKind<'a> {
+    // elements without an upstream image, e.g. `Input`
+    Source(&'a dyn ProcessingElement),
+    // elements consuming a single image on port `IN`
+    Element(&'a dyn ProcessingElement),
+    // elements consuming one image per named input port
+    Multi(&'a dyn MultiInputElement),
+}
+
+struct Node<'a> {
+    name: String,
+    kind: NodeKind<'a>,
+}
+
+impl<'a> Node<'a> {
+    fn input_ports(&self) -> &[&str] {
+        match self.kind {
+            NodeKind::Source(_) => &[],
+            NodeKind::Element(_) => &[IN],
+            NodeKind::Multi(pe) => pe.input_ports(),
+        }
+    }
+
+    fn output_ports(&self) -> &[&str] {
+        &[OUT]
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        match self.kind {
+            NodeKind::Source(_) => &[],
+            NodeKind::Element(pe) => pe.input_formats(),
+            NodeKind::Multi(pe) => pe.input_formats(),
+        }
+    }
+
+    // node name followed by the element type, e.g. "stage_2 (Morphology)"
+    fn label(&self) -> String {
+        let element = match self.kind {
+            NodeKind::Source(pe) | NodeKind::Element(pe) => pe.name(),
+            NodeKind::Multi(pe) => pe.name(),
+        };
+        format!("{} ({})", self.name, element)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Edge {
+    from: NodeId,
+    from_port: String,
+    to: NodeId,
+    to_port: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    UnknownNode(NodeId),
+    UnknownPort {
+        node: String,
+        port: String,
+    },
+    UnconnectedPort {
+        node: String,
+        port: String,
+    },
+    PortConnectedTwice {
+        node: String,
+        port: String,
+    },
+    Cycle(Vec<String>),
+    IncompatibleFormat {
+        producer: String,
+        consumer: String,
+        format: Format,
+        accepted: Vec<Format>,
+    },
+    MismatchedInputs {
+        node: String,
+        inputs: Vec<(String, ImageInfo)>,
+    },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::UnknownNode(id) => write!(f, "node {:?} is not part of this graph", id),
+            GraphError::UnknownPort { node, port } => {
+                write!(f, "node '{}' has no port '{}'", node, port)
+            }
+            GraphError::UnconnectedPort { node, port } => {
+                write!(
+                    f,
+                    "input port '{}' of node '{}' is not connected",
+                    port, node
+                )
+            }
+            GraphError::PortConnectedTwice { node, port } => {
+                write!(
+                    f,
+                    "input port '{}' of node '{}' is connected more than once",
+                    port, node
+                )
+            }
+            GraphError::Cycle(nodes) => {
+                write!(f, "graph contains a cycle involving {}", nodes.join(", "))
+            }
+            GraphError::IncompatibleFormat {
+                producer,
+                consumer,
+                format,
+                accepted,
+            } => {
+                write!(
+                    f,
+                    "'{}' produces {:?} but '{}' only accepts {:?}",
+                    producer, format, consumer, accepted
+                )
+            }
+            GraphError::MismatchedInputs { node, inputs } => {
+                let inputs: Vec<_> = inputs
+                    .iter()
+                    .map(|(producer, info)| {
+                        format!(
+                            "'{}' ({}x{} {:?})",
+                            producer, info.width, info.height, info.format
+                        )
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "inputs of '{}' differ in size or format: {}",
+                    node,
+                    inputs.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+// A directed acyclic graph of processing elements.
+//
+// Nodes are connected from a named output port to a named input port.
+// The graph is validated and recorded into a single command buffer in
+// topological order by `build`.
+#[derive(Default)]
+pub struct PipelineGraph<'a> {
+    nodes: Vec<Node<'a>>,
+    edges: Vec<Edge>,
+    reuse_images: bool,
+    profile: bool,
+}
+
+impl<'a> PipelineGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Lets elements recorded later reuse the images of earlier elements once
+    // these are no longer read. Only the outputs of nodes without any
+    // downstream node are guaranteed to hold their result after execution.
+    pub fn reuse_images(&mut self, reuse: bool) {
+        self.reuse_images = reuse;
+    }
+
+    // Writes a timestamp before and after each element, the durations of the
+    // last execution are read back through `BuiltPipeline::profiler`.
+    pub fn profile(&mut self, enable: bool) {
+        self.profile = enable;
+    }
+
+    pub fn add_source(&mut self, name: &str, element: &'a dyn ProcessingElement) -> NodeId {
+        self.push(name, NodeKind::Source(element))
+    }
+
+    pub fn add(&mut self, name: &str, element: &'a dyn ProcessingElement) -> NodeId {
+        self.push(name, NodeKind::Element(element))
+    }
+
+    pub fn add_multi(&mut self, name: &str, element: &'a dyn MultiInputElement) -> NodeId {
+        self.push(name, NodeKind::Multi(element))
+    }
+
+    fn push(&mut self, name: &str, kind: NodeKind<'a>) -> NodeId {
+        self.nodes.push(Node {
+            name: name.to_string(),
+            kind,
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    // connects the default output port of `from` to the default input port of `to`
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        self.connect_ports(from, OUT, to, IN);
+    }
+
+    pub fn connect_ports(&mut self, from: NodeId, from_port: &str, to: NodeId, to_port: &str) {
+        self.edges.push(Edge {
+            from,
+            from_port: from_port.to_string(),
+            to,
+            to_port: to_port.to_string(),
+        });
+    }
+
+    // appends a linear chain of elements after `from` and returns the last node
+    pub fn add_chain(
+        &mut self,
+        from: NodeId,
+        elements: &[(&str, &'a dyn ProcessingElement)],
+    ) -> NodeId {
+        let mut last = from;
+        for (name, pe) in elements {
+            let node = self.add(name, *pe);
+            self.connect(last, node);
+            last = node;
+        }
+        last
+    }
+
+    pub fn node_name(&self, node: NodeId) -> Option<&str> {
+        self.nodes.get(node.0).map(|n| n.name.as_str())
+    }
+
+    fn node(&self, id: NodeId) -> Result<&Node<'a>, GraphError> {
+        self.nodes.get(id.0).ok_or(GraphError::UnknownNode(id))
+    }
+
+    // returns for every node the upstream node feeding each of its input ports
+    fn resolve_inputs(&self) -> Result<Vec<Vec<NodeId>>, GraphError> {
+        let mut inputs: Vec<Vec<Option<NodeId>>> = self
+            .nodes
+            .iter()
+            .map(|n| vec![None; n.input_ports().len()])
+            .collect();
+
+        for edge in &self.edges {
+            let from = self.node(edge.from)?;
+            let to = self.node(edge.to)?;
+
+            if !from.output_ports().contains(&edge.from_port.as_str()) {
+                return Err(GraphError::UnknownPort {
+                    node: from.name.clone(),
+                    port: edge.from_port.clone(),
+                });
+            }
+
+            let port_index = to
+                .input_ports()
+                .iter()
+                .position(|p| *p == edge.to_port)
+                .ok_or_else(|| GraphError::UnknownPort {
+                    node: to.name.clone(),
+                    port: edge.to_port.clone(),
+                })?;
+
+            let slot = &mut inputs[edge.to.0][port_index];
+            if slot.is_some() {
+                return Err(GraphError::PortConnectedTwice {
+                    node: to.name.clone(),
+                    port: edge.to_port.clone(),
+                });
+            }
+            *slot = Some(edge.from);
+        }
+
+        inputs
+            .into_iter()
+            .zip(&self.nodes)
+            .map(|(ports, node)| {
+                ports
+                    .into_iter()
+                    .zip(node.input_ports())
+                    .map(|(upstream, port)| {
+                        upstream.ok_or_else(|| GraphError::UnconnectedPort {
+                            node: node.name.clone(),
+                            port: port.to_string(),
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Kahn's algorithm, ties are broken by insertion order such that
+    // linear chains are recorded in the order they were added
+    fn topological_order(&self, inputs: &[Vec<NodeId>]) -> Result<Vec<NodeId>, GraphError> {
+        let mut pending: Vec<usize> = inputs.iter().map(|i| i.len()).collect();
+        let mut done = vec![false; self.nodes.len()];
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while order.len() < self.nodes.len() {
+            let next = (0..self.nodes.len()).find(|&i| !done[i] && pending[i] == 0);
+
+            match next {
+                Some(i) => {
+                    done[i] = true;
+                    order.push(NodeId(i));
+
+                    for (consumer, upstream) in inputs.iter().enumerate() {
+                        for u in upstream {
+                            if u.0 == i {
+                                pending[consumer] -= 1;
+                            }
+                        }
+                    }
+                }
+                None => {
+                    let nodes = (0..self.nodes.len())
+                        .filter(|&i| !done[i])
+                        .map(|i| self.nodes[i].name.clone())
+                        .collect();
+                    return Err(GraphError::Cycle(nodes));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+
+    // propagates the image info through the graph and checks that every element
+    // accepts the format produced upstream, unknown infos are not checked
+    fn check_formats(&self, inputs: &[Vec<NodeId>], order: &[NodeId]) -> Result<(), GraphError> {
+        let mut infos: Vec<Option<ImageInfo>> = vec![None; self.nodes.len()];
+
+        for id in order {
+            let node = &self.nodes[id.0];
+            let upstream: Vec<_> = inputs[id.0].iter().map(|u| (*u, infos[u.0])).collect();
+
+            let accepted = node.input_formats();
+            for (producer, info) in &upstream {
+                match info {
+                    Some(info) if !accepted.is_empty() && !accepted.contains(&info.format) => {
+                        return Err(GraphError::IncompatibleFormat {
+                            producer: self.nodes[producer.0].label(),
+                            consumer: node.label(),
+                            format: info.format,
+                            accepted: accepted.to_vec(),
+                        });
+                    }
+                    _ => (),
+                }
+            }
+
+            infos[id.0] = match node.kind {
+                NodeKind::Source(pe) => pe.output_info(None),
+                NodeKind::Element(pe) => pe.output_info(upstream[0].1),
+                NodeKind::Multi(pe) => {
+                    let known: Option<Vec<ImageInfo>> =
+                        upstream.iter().map(|(_, info)| *info).collect();
+
+                    match known {
+                        Some(known) if known.iter().any(|info| *info != known[0]) => {
+                            return Err(GraphError::MismatchedInputs {
+                                node: node.label(),
+                                inputs: upstream
+                                    .iter()
+                                    .zip(known)
+                                    .map(|((producer, _), info)| {
+                                        (self.nodes[producer.0].label(), info)
+                                    })
+                                    .collect(),
+                            });
+                        }
+                        Some(known) => Some(pe.output_info(&known)),
+                        None => None,
+                    }
+                }
+            };
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<Vec<NodeId>, GraphError> {
+        let inputs = self.resolve_inputs()?;
+        let order = self.topological_order(&inputs)?;
+        self.check_formats(&inputs, &order)?;
+        Ok(order)
+    }
+
+    pub fn build(&self, ctx: &VkContext) -> Result<BuiltPipeline, VkcvError> {
+        let inputs = self.resolve_inputs()?;
+        let order = self.topological_order(&inputs)?;
+        self.check_formats(&inputs, &order)?;
+
+        let (recording, pool, fragments, profiler) = self.record(ctx, &inputs, &order)?;
+
+        Ok(BuiltPipeline {
+            recording,
+            names: self.nodes.iter().map(|n| n.name.clone()).collect(),
+            fragments,
+            memory: pool.report(),
+            profiler,
+        })
+    }
+
+    fn record(
+        &self,
+        ctx: &VkContext,
+        inputs: &[Vec<NodeId>],
+        order: &[NodeId],
+    ) -> Result<Recorded, VkcvError> {
+        // the elements take their images from the pool while being recorded
+        let mut builder =
+            AutoCommandBufferBuilder::primary(ctx, ImagePool::new(self.reuse_images))?;
+
+        let mut profiler = match self.profile {
+            true => Some(Profiler::new(ctx, order.len())?),
+            false => None,
+        };
+        if let Some(profiler) = &profiler {
+            profiler.reset(&mut builder)?;
+        }
+
+        // position of the last consumer of each node in the recording order
+        let last_use = image_pool::last_uses(
+            &inputs
+                .iter()
+                .map(|upstream| upstream.iter().map(|u| u.0).collect())
+                .collect::<Vec<_>>(),
+            &order.iter().map(|id| id.0).collect::<Vec<_>>(),
+        );
+
+        let mut fragments = vec![IoFragment::none(); self.nodes.len()];
+
+        for (pos, id) in order.iter().enumerate() {
+            let node = &self.nodes[id.0];
+            if let Some(profiler) = &profiler {
+                profiler.begin(&mut builder)?;
+            }
+
+            let io = match node.kind {
+                NodeKind::Source(pe) => pe.build(ctx, &mut builder, &IoFragment::none()),
+                NodeKind::Element(pe) => {
+                    let upstream = &fragments[inputs[id.0][0].0];
+                    pe.build(ctx, &mut builder, upstream)
+                }
+                NodeKind::Multi(pe) => {
+                    let upstream: Vec<_> = inputs[id.0].iter().map(|u| &fragments[u.0]).collect();
+                    pe.build(ctx, &mut builder, &upstream)
+                }
+            };
+            fragments[id.0] = io.map_err(|e| e.in_element(&node.label()))?;
+
+            if let Some(profiler) = &mut profiler {
+                let fragment = &fragments[id.0];
+                let image = fragment.output_image().or_else(|| fragment.input_image());
+                profiler.end(
+                    &mut builder,
+                    node.label(),
+                    image.as_ref().map(ImageInfo::from),
+                )?;
+            }
+
+            // hand back the images which are not read by any node recorded later,
+            // unless an element passed them through to its output
+            let expired = inputs[id.0]
+                .iter()
+                .filter(|upstream| last_use[upstream.0] == Some(pos))
+                .filter_map(|upstream| fragments[upstream.0].output_image());
+
+            for image in expired {
+                let in_use = order[..=pos].iter().any(|v| {
+                    last_use[v.0].map_or(true, |last| last > pos)
+                        && fragments[v.0]
+                            .output_image()
+                            .map_or(false, |output| Arc::ptr_eq(&output, &image))
+                });
+
+                if !in_use {
+                    utils::release_storage_image(&mut builder, image);
+                }
+            }
+        }
+
+        let (recording, pool) = builder.build()?;
+        Ok((recording, pool, fragments, profiler))
+    }
+}
+
+// command buffer, image pool, fragment of every node and the profiler if enabled
+type Recorded = (Recording, ImagePool, Vec<IoFragment>, Option<Profiler>);
+
+pub struct BuiltPipeline {
+    pub recording: Recording,
+    pub memory: MemoryReport,
+    // set if the graph was built with `profile(true)`
+    pub profiler: Option<Profiler>,
+    names: Vec<String>,
+    fragments: Vec<IoFragment>,
+}
+
+impl BuiltPipeline {
+    pub fn io(&self, node: NodeId) -> &IoFragment {
+        &self.fragments[node.0]
+    }
+
+    pub fn io_by_name(&self, name: &str) -> Option<&IoFragment> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .map(|i| &self.fragments[i])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::processing_elements::AutoCommandBufferBuilder;
+
+    struct Dummy;
+
+    impl ProcessingElement for Dummy {
+        fn build(
+            &self,
+            _ctx: &VkContext,
+            _builder: &mut AutoCommandBufferBuilder,
+            _input: &IoFragment,
+        ) -> Result<IoFragment, VkcvError> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn fan_out_order() {
+        let pe = Dummy;
+        let mut graph = PipelineGraph::new();
+        let input = graph.add_source("input", &pe);
+        let a = graph.add("a", &pe);
+        let b = graph.add("b", &pe);
+        let c = graph.add("c", &pe);
+        graph.connect(c, b);
+        graph.connect(input, a);
+        graph.connect(input, c);
+
+        assert_eq!(graph.validate(), Ok(vec![input, a, c, b]));
+    }
+
+    struct DummyMulti;
+
+    impl MultiInputElement for DummyMulti {
+        fn input_ports(&self) -> &[&'static str] {
+            &["a", "b"]
+        }
+
+        fn build(
+            &self,
+            _ctx: &VkContext,
+            _builder: &mut AutoCommandBufferBuilder,
+            _inputs: &[&IoFragment],
+        ) -> Result<IoFragment, VkcvError> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn join_order() {
+        let pe = Dummy;
+        let join = DummyMulti;
+        let mut graph = PipelineGraph::new();
+        let input = graph.add_source("input", &pe);
+        let diff = graph.add_multi("diff", &join);
+        let a = graph.add("a", &pe);
+        graph.connect_ports(input, OUT, diff, "a");
+        graph.connect_ports(a, OUT, diff, "b");
+        graph.connect(input, a);
+
+        assert_eq!(graph.validate(), Ok(vec![input, a, diff]));
+
+        graph.connect_ports(a, OUT, diff, "c");
+        assert!(matches!(
+            graph.validate(),
+            Err(GraphError::UnknownPort { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_graphs() {
+        let pe = Dummy;
+
+        let mut graph = PipelineGraph::new();
+        graph.add_source("input", &pe);
+        graph.add("a", &pe);
+        assert!(matches!(
+            graph.validate(),
+            Err(GraphError::UnconnectedPort { .. })
+        ));
+
+        let mut graph = PipelineGraph::new();
+        let input = graph.add_source("input", &pe);
+        let a = graph.add("a", &pe);
+        graph.connect(input, a);
+        graph.connect(input, a);
+        assert!(matches!(
+            graph.validate(),
+            Err(GraphError::PortConnectedTwice { .. })
+        ));
+
+        let mut graph = PipelineGraph::new();
+        let a = graph.add("a", &pe);
+        let b = graph.add("b", &pe);
+        graph.connect(a, b);
+        graph.connect(b, a);
+        assert!(matches!(graph.validate(), Err(GraphError::Cycle(_))));
+
+        let mut graph = PipelineGraph::new();
+        let a = graph.add_source("a", &pe);
+        let b = graph.add("b", &pe);
+        graph.connect_ports(a, "mask", b, IN);
+        assert!(matches!(
+            graph.validate(),
+            Err(GraphError::UnknownPort { .. })
+        ));
+    }
+
+    // accepts `accepts` (any if empty) and produces `produces` if set
+    struct FormatDummy {
+        accepts: Vec<Format>,
+        produces: Option<ImageInfo>,
+    }
+
+    impl ProcessingElement for FormatDummy {
+        fn build(
+            &self,
+            _ctx: &VkContext,
+            _builder: &mut AutoCommandBufferBuilder,
+            _input: &IoFragment,
+        ) -> Result<IoFragment, VkcvError> {
+            unreachable!()
+        }
+
+        fn input_formats(&self) -> &[Format] {
+            &self.accepts
+        }
+
+        fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+            self.produces.or(input)
+        }
+    }
+
+    #[test]
+    fn format_checks() {
+        let rgba8 = ImageInfo {
+            width: 64,
+            height: 32,
+            format: Format::R8G8B8A8_UNORM,
+        };
+        let source = FormatDummy {
+            accepts: vec![],
+            produces: Some(rgba8),
+        };
+        let to_r8 = FormatDummy {
+            accepts: vec![Format::R8G8B8A8_UNORM],
+            produces: Some(ImageInfo {
+                format: Format::R8_UNORM,
+                ..rgba8
+            }),
+        };
+        let r8_only = FormatDummy {
+            accepts: vec![Format::R8_UNORM],
+            produces: None,
+        };
+
+        let mut graph = PipelineGraph::new();
+        let input = graph.add_source("input", &source);
+        graph.add_chain(input, &[("a", &to_r8), ("b", &r8_only)]);
+        assert!(graph.validate().is_ok());
+
+        let c = graph.add("c", &r8_only);
+        graph.connect(input, c);
+        assert_eq!(
+            graph.validate(),
+            Err(GraphError::IncompatibleFormat {
+                producer: "input (FormatDummy)".to_string(),
+                consumer: "c (FormatDummy)".to_string(),
+                format: Format::R8G8B8A8_UNORM,
+                accepted: vec![Format::R8_UNORM],
+            })
+        );
+
+        // joining images of different formats
+        let join = DummyMulti;
+        let mut graph = PipelineGraph::new();
+        let input = graph.add_source("input", &source);
+        let b = graph.add("b", &to_r8);
+        let diff = graph.add_multi("diff", &join);
+        graph.connect(input, b);
+        graph.connect_ports(input, OUT, diff, "a");
+        graph.connect_ports(b, OUT, diff, "b");
+        assert!(matches!(
+            graph.validate(),
+            Err(GraphError::MismatchedInputs { .. })
+        ));
+    }
+}
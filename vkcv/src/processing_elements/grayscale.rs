@@ -2,10 +2,11 @@ use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
     format::Format,
     image::{view::ImageView, ImageAccess},
-    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    pipeline::{Pipeline, PipelineBindPoint},
 };
 
 use crate::{
+    error::VkcvError,
     utils::{self, ImageInfo},
     vk_init::VkContext,
 };
@@ -33,30 +34,23 @@ impl ProcessingElement for Grayscale {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
-        let pipeline = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs::SpecializationConstants {},
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+    ) -> Result<IoFragment, VkcvError> {
+        let pipeline = utils::compute_pipeline(ctx, cs::load, &cs::SpecializationConstants {})?;
 
         // input image
-        let input_img = input.output_image().unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
 
         // output image
-        let output_img =
-            utils::create_storage_image(ctx, &ImageInfo::from_image(&input_img, Format::R8_UNORM));
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo::from_image(&input_img, Format::R8_UNORM),
+        )?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = PersistentDescriptorSet::new(
             &ctx.memory.descriptor_set_allocator,
@@ -65,8 +59,7 @@ impl ProcessingElement for Grayscale {
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
             ],
-        )
-        .unwrap();
+        )?;
 
         // build command buffer
         builder
@@ -80,13 +73,23 @@ impl ProcessingElement for Grayscale {
             .dispatch(utils::workgroups(
                 &input_img.dimensions().width_height(),
                 &[16, 16],
-            ))
-            .unwrap();
+            ))?;
 
-        IoFragment {
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label: utils::basic_label("Grayscale", &output_img),
-        }
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8G8B8A8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            format: Format::R8_UNORM,
+            ..info
+        })
     }
 }
@@ -1,10 +1,12 @@
 use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
     image::{view::ImageView, ImageAccess},
-    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    pipeline::{Pipeline, PipelineBindPoint},
 };
 
 use crate::{
+    error::VkcvError,
     utils::{self},
     vk_init::VkContext,
 };
@@ -32,29 +34,19 @@ impl ProcessingElement for Hsvconv {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
-        let pipeline = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs::SpecializationConstants {},
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+    ) -> Result<IoFragment, VkcvError> {
+        let pipeline = utils::compute_pipeline(ctx, cs::load, &cs::SpecializationConstants {})?;
 
         // input image
-        let input_img = input.output_image().unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
 
         // output image
-        let output_img = utils::create_storage_image(ctx, &(&input_img).into());
+        let output_img = utils::create_storage_image(ctx, builder, &(&input_img).into())?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = PersistentDescriptorSet::new(
             &ctx.memory.descriptor_set_allocator,
@@ -63,8 +55,7 @@ impl ProcessingElement for Hsvconv {
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
             ],
-        )
-        .unwrap();
+        )?;
 
         // build command buffer
         builder
@@ -78,13 +69,16 @@ impl ProcessingElement for Hsvconv {
             .dispatch(utils::workgroups(
                 &input_img.dimensions().width_height(),
                 &[16, 16],
-            ))
-            .unwrap();
+            ))?;
 
-        IoFragment {
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label: utils::basic_label("HSV conversion", &output_img),
-        }
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8G8B8A8_UNORM]
     }
 }
@@ -4,6 +4,7 @@ use vulkano::{
 };
 
 use crate::{
+    error::VkcvError,
     utils::{create_storage_image, ImageInfo},
     vk_init::VkContext,
 };
@@ -26,9 +27,9 @@ impl ProcessingElement for Input {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         _input: &IoFragment,
-    ) -> IoFragment {
+    ) -> Result<IoFragment, VkcvError> {
         // output image
-        let output_img = create_storage_image(ctx, &self.input_format);
+        let output_img = create_storage_image(ctx, builder, &self.input_format)?;
 
         let count = self.input_format.bytes_count();
         let input_buffer = CpuAccessibleBuffer::from_iter(
@@ -42,23 +43,24 @@ impl ProcessingElement for Input {
             },
             true,
             (0..count).map(|_| 0u8),
-        )
-        .unwrap();
+        )?;
 
         // build command buffer
 
-        builder
-            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
-                input_buffer.clone(),
-                output_img.clone(),
-            ))
-            .unwrap();
+        builder.copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+            input_buffer.clone(),
+            output_img.clone(),
+        ))?;
 
-        IoFragment {
+        Ok(IoFragment {
             input: Io::Buffer(input_buffer),
             output: Io::Image(output_img),
             label: "Input".to_string(),
-        }
+        })
+    }
+
+    fn output_info(&self, _input: Option<ImageInfo>) -> Option<ImageInfo> {
+        Some(self.input_format)
     }
 }
 
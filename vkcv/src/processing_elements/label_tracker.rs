@@ -0,0 +1,208 @@
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    endpoints::image_download::TransferredImage,
+    error::VkcvError,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{
+    color_classifier::MAX_CLASSES, AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement,
+};
+
+// 1st pass: sums per tile and class
+mod cs_tiles {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/label_tracker_tiles.comp.glsl",
+    }
+}
+
+// 2nd pass: sums over all tiles, one workgroup per class
+mod cs_reduce {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/label_tracker_reduce.comp.glsl",
+    }
+}
+
+// see label_tracker_tiles.comp.glsl
+const TILE_SIZE: u32 = 16;
+
+// Centroid and area of every class of a label image, e.g. as produced by
+// `ColorClassifier`.
+//
+// The result is a `classes`x1 rgba32f image, use `centroids` to read it.
+pub struct LabelTracker {
+    classes: u32,
+}
+
+impl LabelTracker {
+    pub fn new(classes: u32) -> Self {
+        Self { classes }
+    }
+}
+
+impl ProcessingElement for LabelTracker {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        if self.classes == 0 || self.classes as usize > MAX_CLASSES {
+            return Err(VkcvError::InvalidParameters(format!(
+                "expected 1 to {} classes, got {}",
+                MAX_CLASSES, self.classes
+            )));
+        }
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let [width, height] = input_img.dimensions().width_height();
+        let [tiles_x, tiles_y, _] = utils::workgroups(&[width, height], &[TILE_SIZE, TILE_SIZE]);
+
+        let tiles_pipeline = utils::compute_pipeline(
+            ctx,
+            cs_tiles::load,
+            &cs_tiles::SpecializationConstants {
+                classes: self.classes as i32,
+            },
+        )?;
+        let reduce_pipeline = utils::compute_pipeline(
+            ctx,
+            cs_reduce::load,
+            &cs_reduce::SpecializationConstants {
+                inv_width: 1.0 / width as f32,
+                inv_height: 1.0 / height as f32,
+            },
+        )?;
+
+        // the sums per tile, one block of rows per class
+        let tiles_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                width: tiles_x,
+                height: tiles_y * self.classes,
+                format: Format::R32G32B32A32_SFLOAT,
+            },
+        )?;
+
+        // output image
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                width: self.classes,
+                height: 1,
+                format: Format::R32G32B32A32_SFLOAT,
+            },
+        )?;
+
+        // setup layout
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let tiles_img_view = ImageView::new_default(tiles_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        let set_tiles = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            tiles_pipeline
+                .layout()
+                .set_layouts()
+                .get(0)
+                .unwrap()
+                .clone(),
+            [
+                WriteDescriptorSet::image_view(0, input_img_view),
+                WriteDescriptorSet::image_view(1, tiles_img_view.clone()),
+            ],
+        )?;
+
+        let set_reduce = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            reduce_pipeline
+                .layout()
+                .set_layouts()
+                .get(0)
+                .unwrap()
+                .clone(),
+            [
+                WriteDescriptorSet::image_view(0, tiles_img_view),
+                WriteDescriptorSet::image_view(1, output_img_view),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(tiles_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                tiles_pipeline.layout().clone(),
+                0,
+                set_tiles,
+            )
+            .dispatch([tiles_x, tiles_y, 1])?
+            .bind_pipeline_compute(reduce_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                reduce_pipeline.layout().clone(),
+                0,
+                set_reduce,
+            )
+            .dispatch([self.classes, 1, 1])?;
+
+        utils::release_storage_image(builder, tiles_img);
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("LabelTracker", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|_| ImageInfo {
+            width: self.classes,
+            height: 1,
+            format: Format::R32G32B32A32_SFLOAT,
+        })
+    }
+}
+
+// the normalized centroid and the area (as fraction of the image) per class,
+// the centroid is NaN if the class was not found
+pub fn centroids(tf_img: &TransferredImage) -> Result<Vec<([f32; 2], f32)>, VkcvError> {
+    let info = tf_img.info();
+    if info.format != Format::R32G32B32A32_SFLOAT {
+        return Err(VkcvError::UnsupportedFormat(info.format));
+    }
+    if info.height != 1 {
+        return Err(VkcvError::InvalidParameters(format!(
+            "expected one row of centroids, got {}",
+            info.height
+        )));
+    }
+
+    Ok(tf_img
+        .buffer_content()
+        .chunks_exact(16)
+        .map(|pixel| {
+            let f =
+                |i: usize| f32::from_le_bytes([pixel[i], pixel[i + 1], pixel[i + 2], pixel[i + 3]]);
+            let (x, y, z) = (f(0), f(4), f(8));
+
+            ([x / z, y / z], z)
+        })
+        .collect())
+}
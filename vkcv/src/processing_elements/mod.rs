@@ -1,35 +1,227 @@
+pub mod background_subtractor;
+pub mod binary_op;
+pub mod canny;
+pub mod color_classifier;
 pub mod color_filter;
+pub mod connected_components;
 pub mod convolution;
 pub mod convolution_2p;
+pub mod frame_difference;
+pub mod gaussian_blur;
+pub mod graph;
 pub mod grayscale;
 pub mod hsvconv;
 pub mod input;
+pub mod label_tracker;
 pub mod morphology;
+pub mod morphology_ex;
 pub mod output;
 pub mod pooling;
+pub mod roi;
+pub mod sobel;
 pub mod tracker;
 
-use std::sync::Arc;
+use std::{
+    any::Any,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
 use vulkano::{
     buffer::CpuAccessibleBuffer,
-    command_buffer::{allocator::StandardCommandBufferAllocator, PrimaryAutoCommandBuffer},
+    command_buffer::{
+        allocator::StandardCommandBufferAllocator, CommandBufferUsage, PrimaryAutoCommandBuffer,
+    },
+    format::Format,
     image::StorageImage,
+    sync::{self, FenceSignalFuture, GpuFuture},
 };
 
-use crate::vk_init::VkContext;
+use crate::{
+    error::VkcvError,
+    image_pool::{ImagePool, MemoryReport},
+    utils::ImageInfo,
+    vk_init::VkContext,
+};
 
-pub type AutoCommandBufferBuilder = vulkano::command_buffer::AutoCommandBufferBuilder<
+type VkAutoCommandBufferBuilder = vulkano::command_buffer::AutoCommandBufferBuilder<
     PrimaryAutoCommandBuffer,
     Arc<StandardCommandBufferAllocator>,
 >;
 
+type SubmitHook = Arc<dyn Fn() -> Result<(), VkcvError> + Send + Sync>;
+
+// The command buffer a pipeline is recorded into, derefs to the vulkano builder.
+//
+// It also carries the image pool the elements take their storage images from,
+// see `utils::create_storage_image`, and the hooks to run before each
+// submission of the command buffer.
+pub struct AutoCommandBufferBuilder {
+    inner: VkAutoCommandBufferBuilder,
+    image_pool: ImagePool,
+    hooks: Vec<SubmitHook>,
+}
+
+impl AutoCommandBufferBuilder {
+    // a command buffer which can be submitted any number of times
+    pub fn primary(ctx: &VkContext, image_pool: ImagePool) -> Result<Self, VkcvError> {
+        let inner = VkAutoCommandBufferBuilder::primary(
+            &ctx.memory.command_buffer_allocator,
+            ctx.queue.queue_family_index(),
+            CommandBufferUsage::MultipleSubmit,
+        )?;
+
+        Ok(Self {
+            inner,
+            image_pool,
+            hooks: Vec::new(),
+        })
+    }
+
+    pub fn image_pool(&mut self) -> &mut ImagePool {
+        &mut self.image_pool
+    }
+
+    // runs `hook` before every submission, i.e. once the previous execution of
+    // the command buffer has finished
+    pub fn on_submit(&mut self, hook: impl Fn() -> Result<(), VkcvError> + Send + Sync + 'static) {
+        self.hooks.push(Arc::new(hook));
+    }
+
+    pub fn build(self) -> Result<(Recording, ImagePool), VkcvError> {
+        let recording = Recording {
+            cb: Arc::new(self.inner.build()?),
+            hooks: self.hooks,
+            memory: self.image_pool.report(),
+        };
+        Ok((recording, self.image_pool))
+    }
+}
+
+impl Deref for AutoCommandBufferBuilder {
+    type Target = VkAutoCommandBufferBuilder;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for AutoCommandBufferBuilder {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+// A recorded pipeline.
+//
+// The command buffer must be submitted through `submit` resp. `submit_after`,
+// or after calling `prepare`, such that e.g. parameters changed while it was
+// executing are written beforehand.
+#[derive(Clone)]
+pub struct Recording {
+    pub cb: Arc<PrimaryAutoCommandBuffer>,
+    hooks: Vec<SubmitHook>,
+    memory: MemoryReport,
+}
+
+impl Recording {
+    // the storage images taken from the image pool while recording
+    pub fn memory(&self) -> MemoryReport {
+        self.memory
+    }
+
+    // must not be called while the command buffer is executing
+    pub fn prepare(&self) -> Result<(), VkcvError> {
+        for hook in &self.hooks {
+            hook()?;
+        }
+        Ok(())
+    }
+
+    pub fn submit(
+        &self,
+        ctx: &VkContext,
+    ) -> Result<FenceSignalFuture<Box<dyn GpuFuture + Send>>, VkcvError> {
+        self.submit_after(ctx, sync::now(ctx.device.clone()).boxed_send())
+    }
+
+    // executes the command buffer once `future` is done
+    pub fn submit_after(
+        &self,
+        ctx: &VkContext,
+        future: Box<dyn GpuFuture + Send>,
+    ) -> Result<FenceSignalFuture<Box<dyn GpuFuture + Send>>, VkcvError> {
+        self.prepare()?;
+
+        Ok(future
+            .then_execute(ctx.queue.clone(), self.cb.clone())?
+            .boxed_send()
+            .then_signal_fence_and_flush()?)
+    }
+}
+
 pub trait ProcessingElement {
     fn build(
         &self,
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment;
+    ) -> Result<IoFragment, VkcvError>;
+
+    // formats accepted on the input, empty if any format is accepted
+    fn input_formats(&self) -> &[Format] {
+        &[]
+    }
+
+    // the image produced for the given input image, `None` if unknown
+    // used to validate a pipeline before any commands are recorded
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input
+    }
+
+    // used to tell which element failed
+    fn name(&self) -> &'static str {
+        short_type_name::<Self>()
+    }
+
+    // the handle to the parameters of the element (e.g. `Param<Window>` of
+    // `Roi`), used to look up elements created from a description
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        None
+    }
+
+    // discards the state of the element (e.g. a background model) with the
+    // next submission, nothing to do for stateless elements
+    fn reset(&self) {}
+}
+
+// elements combining several upstream images, one per named input port
+// all input images are required to share the same size and format
+pub trait MultiInputElement {
+    fn input_ports(&self) -> &[&'static str];
+
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        inputs: &[&IoFragment],
+    ) -> Result<IoFragment, VkcvError>;
+
+    fn input_formats(&self) -> &[Format] {
+        &[]
+    }
+
+    fn output_info(&self, inputs: &[ImageInfo]) -> ImageInfo {
+        inputs[0]
+    }
+
+    fn name(&self) -> &'static str {
+        short_type_name::<Self>()
+    }
+}
+
+fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
 }
 
 // marker trait
@@ -1,32 +1,220 @@
+use std::{any::Any, sync::Arc};
+
+use serde::{Deserialize, Serialize};
 use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
-    image::{view::ImageView, ImageAccess},
-    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    format::Format,
+    image::{view::ImageView, ImageAccess, StorageImage},
+    pipeline::{Pipeline, PipelineBindPoint},
 };
 
-use crate::{utils, vk_init::VkContext};
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
 
 use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
 
 mod cs {
     vulkano_shaders::shader! {
         ty: "compute",
-        path: "src/shaders/morphology3x3.comp.glsl",
+        path: "src/shaders/morphology.comp.glsl",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Operation {
     Erode,
     Dilate,
 }
 
+impl Parameters for Operation {
+    fn encode(&self) -> Vec<u8> {
+        let erode_dilate = match self {
+            Operation::Erode => 0,
+            Operation::Dilate => 1,
+        };
+
+        bytemuck::bytes_of(&cs::ty::Params { erode_dilate }).to_vec()
+    }
+}
+
+// limited by the size of the mask in the uniform block, rectangles and
+// crosses can be of any size
+pub const MAX_MASK_SIZE: usize = 15;
+
+// rectangles larger than this are processed in two passes
+const MAX_SINGLE_PASS_RECT: u32 = 5;
+
+// The neighborhood taken into account, centered on the pixel.
+//
+// Width and height must be odd, e.g. in TOML
+//
+//     element = { shape = "ellipse", width = 7, height = 5 }
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case", deny_unknown_fields)]
+pub enum StructuringElement {
+    Rect { width: u32, height: u32 },
+    Cross { width: u32, height: u32 },
+    // the ellipse inscribed into the rectangle, a 3x3 ellipse is a cross
+    Ellipse { width: u32, height: u32 },
+    // row major, i.e. mask[y][x], non-zero entries are part of the element
+    Custom { mask: Vec<Vec<u8>> },
+}
+
+impl Default for StructuringElement {
+    fn default() -> Self {
+        StructuringElement::Rect {
+            width: 3,
+            height: 3,
+        }
+    }
+}
+
+impl StructuringElement {
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            StructuringElement::Rect { width, height }
+            | StructuringElement::Cross { width, height }
+            | StructuringElement::Ellipse { width, height } => (*width, *height),
+            StructuringElement::Custom { mask } => (
+                mask.first().map_or(0, |row| row.len()) as u32,
+                mask.len() as u32,
+            ),
+        }
+    }
+
+    pub fn mask(&self) -> Vec<Vec<bool>> {
+        let (width, height) = self.size();
+        let (rx, ry) = ((width / 2) as f32, (height / 2) as f32);
+
+        (0..height as usize)
+            .map(|y| {
+                (0..width as usize)
+                    .map(|x| {
+                        let (dx, dy) = (x as f32 - rx, y as f32 - ry);
+                        match self {
+                            StructuringElement::Rect { .. } => true,
+                            StructuringElement::Cross { .. } => dx == 0.0 || dy == 0.0,
+                            StructuringElement::Ellipse { .. } => {
+                                // degenerated ellipses are lines
+                                let nx = if rx > 0.0 { dx / rx } else { 0.0 };
+                                let ny = if ry > 0.0 { dy / ry } else { 0.0 };
+                                nx * nx + ny * ny <= 1.0
+                            }
+                            StructuringElement::Custom { mask } => mask[y][x] != 0,
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // rectangles are eroded resp. dilated along x and y in two passes
+    pub fn is_separable(&self) -> bool {
+        matches!(self, StructuringElement::Rect { width, height }
+            if *width > MAX_SINGLE_PASS_RECT || *height > MAX_SINGLE_PASS_RECT)
+    }
+
+    pub fn validate(&self) -> Result<(), VkcvError> {
+        let (width, height) = self.size();
+
+        if width % 2 == 0 || height % 2 == 0 {
+            return Err(VkcvError::InvalidParameters(format!(
+                "structuring element size {}x{} must be odd",
+                width, height
+            )));
+        }
+
+        let masked = match self {
+            StructuringElement::Rect { .. } => !self.is_separable(),
+            StructuringElement::Cross { .. } => false,
+            _ => true,
+        };
+        if masked && (width as usize > MAX_MASK_SIZE || height as usize > MAX_MASK_SIZE) {
+            return Err(VkcvError::InvalidParameters(format!(
+                "structuring element size {}x{} exceeds {}x{}",
+                width, height, MAX_MASK_SIZE, MAX_MASK_SIZE
+            )));
+        }
+
+        if let StructuringElement::Custom { mask } = self {
+            if mask.iter().any(|row| row.len() != width as usize) {
+                return Err(VkcvError::InvalidParameters(
+                    "all rows of the mask must have the same length".to_string(),
+                ));
+            }
+            if mask.iter().flatten().all(|v| *v == 0) {
+                return Err(VkcvError::InvalidParameters("empty mask".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Parameters for StructuringElement {
+    fn encode(&self) -> Vec<u8> {
+        let (width, height) = self.size();
+
+        let shape = match self {
+            _ if self.is_separable() => 2,
+            StructuringElement::Cross { .. } => 1,
+            _ => 0,
+        };
+
+        let mut mask = [[0u32; 4]; 2];
+        if shape == 0 {
+            for (i, set) in self.mask().iter().flatten().enumerate() {
+                if *set && i < 256 {
+                    mask[i / 128][(i / 32) % 4] |= 1 << (i % 32);
+                }
+            }
+        }
+
+        bytemuck::bytes_of(&cs::ty::StructuringElement {
+            mask,
+            shape,
+            width: width as i32,
+            height: height as i32,
+        })
+        .to_vec()
+    }
+
+    fn validate(&self) -> Result<(), VkcvError> {
+        StructuringElement::validate(self)
+    }
+}
+
 pub struct Morphology {
-    op: Operation,
+    op: Param<Operation>,
+    element: Param<StructuringElement>,
 }
 
 impl Morphology {
+    // 3x3 rectangle
     pub fn new(op: Operation) -> Self {
-        Self { op }
+        Self::with_element(op, StructuringElement::default())
+    }
+
+    pub fn with_element(op: Operation, element: StructuringElement) -> Self {
+        Self {
+            op: Param::new(op),
+            element: Param::new(element),
+        }
+    }
+
+    // the operation can be changed after the pipeline has been built
+    pub fn params(&self) -> Param<Operation> {
+        self.op.clone()
     }
 }
 
@@ -36,76 +224,156 @@ impl ProcessingElement for Morphology {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
+    ) -> Result<IoFragment, VkcvError> {
         let local_size = 16;
 
-        let pipeline = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
+        let element = self.element.get();
+        element.validate()?;
+
+        let pipeline = |second_pass| {
+            utils::compute_pipeline(
+                ctx,
+                cs::load,
                 &cs::SpecializationConstants {
                     constant_0: local_size,
                     constant_1: local_size,
-                    erode_dilate: match self.op {
-                        Operation::Erode => 0,
-                        Operation::Dilate => 1,
-                    },
-                    ..Default::default()
+                    second_pass,
                 },
-                None,
-                |_| {},
             )
-            .unwrap()
         };
 
         // input image
-        let input_img = input.output_image().unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let info = ImageInfo::from(&input_img);
 
         // output image
-        let output_img = utils::create_storage_image(ctx, &(&input_img).into());
+        let output_img = utils::create_storage_image(ctx, builder, &info)?;
 
-        // setup layout
-        let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let op = self.op.bind(ctx, builder)?;
+        let element_params = self.element.bind(ctx, builder)?;
 
-        let set = PersistentDescriptorSet::new(
-            &ctx.memory.descriptor_set_allocator,
-            layout.clone(),
-            [
-                WriteDescriptorSet::image_view(0, input_img_view),
-                WriteDescriptorSet::image_view(1, output_img_view),
-            ],
-        )
-        .unwrap();
-
-        // build command buffer
-        // let push_constants = cs::ty::PushConstants {
-        //     kernel: [-1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 2.0],
-        //     offset: 1.0,
-        //     denom: 0.5,
-        // };
-
-        builder
-            .bind_pipeline_compute(pipeline.clone())
-            .bind_descriptor_sets(
-                PipelineBindPoint::Compute,
-                pipeline.layout().clone(),
-                0,
-                set.clone(),
-            )
-            // .push_constants(pipeline.layout().clone(), 0, push_constants)
-            .dispatch(utils::workgroups(
-                &input_img.dimensions().width_height(),
-                &[local_size, local_size],
-            ))
-            .unwrap();
-
-        IoFragment {
+        // one pass from src to dst
+        let dispatch = |builder: &mut AutoCommandBufferBuilder,
+                        src: &Arc<StorageImage>,
+                        dst: &Arc<StorageImage>,
+                        second_pass: i32|
+         -> Result<(), VkcvError> {
+            let pipeline = pipeline(second_pass)?;
+
+            let layout = pipeline.layout().set_layouts().get(0).unwrap();
+            let set = PersistentDescriptorSet::new(
+                &ctx.memory.descriptor_set_allocator,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view(0, ImageView::new_default(src.clone())?),
+                    WriteDescriptorSet::image_view(1, ImageView::new_default(dst.clone())?),
+                    WriteDescriptorSet::buffer(2, op.clone()),
+                    WriteDescriptorSet::buffer(3, element_params.clone()),
+                ],
+            )?;
+
+            builder
+                .bind_pipeline_compute(pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    pipeline.layout().clone(),
+                    0,
+                    set,
+                )
+                .dispatch(utils::workgroups(
+                    &src.dimensions().width_height(),
+                    &[local_size, local_size],
+                ))?;
+
+            Ok(())
+        };
+
+        if element.is_separable() {
+            // the first pass writes the transposed image
+            let intermediate_img = utils::create_storage_image(
+                ctx,
+                builder,
+                &ImageInfo {
+                    width: info.height,
+                    height: info.width,
+                    format: info.format,
+                },
+            )?;
+
+            dispatch(builder, &input_img, &intermediate_img, 0)?;
+            dispatch(builder, &intermediate_img, &output_img, 1)?;
+
+            utils::release_storage_image(builder, intermediate_img);
+        } else {
+            dispatch(builder, &input_img, &output_img, 0)?;
+        }
+
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label: utils::basic_label("Morphology", &output_img),
-        }
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn masks() {
+        let ellipse = StructuringElement::Ellipse {
+            width: 3,
+            height: 3,
+        };
+        let cross = StructuringElement::Cross {
+            width: 3,
+            height: 3,
+        };
+        assert_eq!(ellipse.mask(), cross.mask());
+        assert_eq!(
+            cross.mask(),
+            [
+                [false, true, false],
+                [true, true, true],
+                [false, true, false]
+            ]
+        );
+
+        let rect = StructuringElement::Rect {
+            width: 31,
+            height: 1,
+        };
+        assert!(rect.is_separable());
+        assert!(rect.validate().is_ok());
+        assert!(!StructuringElement::default().is_separable());
+    }
+
+    #[test]
+    fn invalid_elements() {
+        let even = StructuringElement::Rect {
+            width: 4,
+            height: 3,
+        };
+        assert!(even.validate().is_err());
+
+        let large = StructuringElement::Ellipse {
+            width: 17,
+            height: 17,
+        };
+        assert!(large.validate().is_err());
+
+        let empty = StructuringElement::Custom {
+            mask: vec![vec![0; 3]; 3],
+        };
+        assert!(empty.validate().is_err());
     }
 }
@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use vulkano::{format::Format, image::StorageImage};
+
+use crate::{error::VkcvError, utils, vk_init::VkContext};
+
+use super::{
+    binary_op,
+    morphology::{self, Morphology, StructuringElement},
+    AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    // dilate(erode(src))
+    Open,
+    // erode(dilate(src))
+    Close,
+    // dilate(src) - erode(src)
+    Gradient,
+    // src - open(src)
+    TopHat,
+    // close(src) - src
+    BlackHat,
+}
+
+// Compound morphological operations as in OpenCV's `morphologyEx`.
+//
+// Erosion and dilation are each applied `iterations` times, e.g. an opening
+// with 2 iterations erodes twice and then dilates twice.
+pub struct MorphologyEx {
+    op: Operation,
+    erode: Morphology,
+    dilate: Morphology,
+    iterations: u32,
+}
+
+impl MorphologyEx {
+    // 3x3 rectangle, single iteration
+    pub fn new(op: Operation) -> Self {
+        Self::with_element(op, StructuringElement::default(), 1)
+    }
+
+    pub fn with_element(op: Operation, element: StructuringElement, iterations: u32) -> Self {
+        Self {
+            op,
+            erode: Morphology::with_element(morphology::Operation::Erode, element.clone()),
+            dilate: Morphology::with_element(morphology::Operation::Dilate, element),
+            iterations: iterations.max(1),
+        }
+    }
+
+    // the erosions resp. dilations applied one after the other
+    fn passes(
+        &self,
+        first: morphology::Operation,
+        second: Option<morphology::Operation>,
+    ) -> Operand {
+        let n = self.iterations as usize;
+        let mut passes = vec![first; n];
+        passes.extend(second.into_iter().flat_map(|op| vec![op; n]));
+        Operand::Passes(passes)
+    }
+
+    // the result is `minuend - subtrahend`, resp. `minuend` only
+    fn plan(&self) -> (Operand, Option<Operand>) {
+        use morphology::Operation::{Dilate, Erode};
+
+        let open = || self.passes(Erode, Some(Dilate));
+        let close = || self.passes(Dilate, Some(Erode));
+
+        match self.op {
+            Operation::Open => (open(), None),
+            Operation::Close => (close(), None),
+            Operation::Gradient => (self.passes(Dilate, None), Some(self.passes(Erode, None))),
+            Operation::TopHat => (Operand::Input, Some(open())),
+            Operation::BlackHat => (close(), Some(Operand::Input)),
+        }
+    }
+
+    // all intermediate images are released
+    fn record(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        operand: &Operand,
+        input: &IoFragment,
+    ) -> Result<Arc<StorageImage>, VkcvError> {
+        let passes = match operand {
+            Operand::Input => return image(input),
+            Operand::Passes(passes) => passes,
+        };
+
+        let mut fragment = input.clone();
+        for (i, op) in passes.iter().enumerate() {
+            let element = match op {
+                morphology::Operation::Erode => &self.erode,
+                morphology::Operation::Dilate => &self.dilate,
+            };
+            let next = element.build(ctx, builder, &fragment)?;
+            if i > 0 {
+                utils::release_storage_image(builder, image(&fragment)?);
+            }
+            fragment = next;
+        }
+
+        image(&fragment)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Operand {
+    Input,
+    Passes(Vec<morphology::Operation>),
+}
+
+fn image(fragment: &IoFragment) -> Result<Arc<StorageImage>, VkcvError> {
+    fragment.output_image().ok_or(VkcvError::MissingImage)
+}
+
+impl ProcessingElement for MorphologyEx {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        // input image
+        let input_img = image(input)?;
+
+        let (minuend, subtrahend) = self.plan();
+        let a = self.record(ctx, builder, &minuend, input)?;
+
+        let output_img = match subtrahend {
+            Some(subtrahend) => {
+                let b = self.record(ctx, builder, &subtrahend, input)?;
+                let output_img =
+                    binary_op::record(ctx, builder, binary_op::Operation::Subtract.code(), &a, &b)?;
+
+                // the input is owned by the upstream element
+                for (img, operand) in [(a, minuend), (b, subtrahend)] {
+                    if operand != Operand::Input {
+                        utils::release_storage_image(builder, img);
+                    }
+                }
+
+                output_img
+            }
+            None => a,
+        };
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label(&format!("MorphologyEx ({:?})", self.op), &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::morphology::Operation::{Dilate, Erode};
+    use super::*;
+
+    fn plan(op: Operation, iterations: u32) -> (Operand, Option<Operand>) {
+        MorphologyEx::with_element(op, StructuringElement::default(), iterations).plan()
+    }
+
+    #[test]
+    fn passes() {
+        assert_eq!(
+            plan(Operation::Open, 2),
+            (Operand::Passes(vec![Erode, Erode, Dilate, Dilate]), None)
+        );
+        assert_eq!(
+            plan(Operation::Close, 1),
+            (Operand::Passes(vec![Dilate, Erode]), None)
+        );
+        assert_eq!(
+            plan(Operation::Gradient, 2),
+            (
+                Operand::Passes(vec![Dilate, Dilate]),
+                Some(Operand::Passes(vec![Erode, Erode]))
+            )
+        );
+        assert_eq!(
+            plan(Operation::TopHat, 1),
+            (Operand::Input, Some(Operand::Passes(vec![Erode, Dilate])))
+        );
+        assert_eq!(
+            plan(Operation::BlackHat, 1),
+            (Operand::Passes(vec![Dilate, Erode]), Some(Operand::Input))
+        );
+
+        // at least one iteration
+        assert_eq!(plan(Operation::Open, 0), plan(Operation::Open, 1));
+    }
+}
@@ -4,7 +4,7 @@ use vulkano::{
     image::ImageAccess,
 };
 
-use crate::vk_init::VkContext;
+use crate::{error::VkcvError, utils::ImageInfo, vk_init::VkContext};
 
 use super::{AutoCommandBufferBuilder, Io, IoFragment, PipeOutput, ProcessingElement};
 
@@ -28,44 +28,45 @@ impl ProcessingElement for Output {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
+    ) -> Result<IoFragment, VkcvError> {
         // input image
-        if let Some(input_img) = input.output_image() {
-            // output buffer (cpu accessible)
-            let depth = (input_img.format().components().iter().sum::<u8>() / 8) as u32;
-            let count = input_img.dimensions().width()
-                * input_img.dimensions().height()
-                * input_img.dimensions().depth()
-                * depth;
-            let output_buffer = CpuAccessibleBuffer::from_iter(
-                &ctx.memory.allocator,
-                BufferUsage {
-                    transfer_src: true,
-                    transfer_dst: true,
-                    uniform_buffer: true,
-                    storage_buffer: true,
-                    ..Default::default()
-                },
-                true,
-                (0..count).map(|_| 0u8),
-            )
-            .unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
 
-            // build command buffer
-            builder
-                .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
-                    input_img.clone(),
-                    output_buffer.clone(),
-                ))
-                .unwrap();
+        // output buffer (cpu accessible)
+        let depth = (input_img.format().components().iter().sum::<u8>() / 8) as u32;
+        let count = input_img.dimensions().width()
+            * input_img.dimensions().height()
+            * input_img.dimensions().depth()
+            * depth;
+        let output_buffer = CpuAccessibleBuffer::from_iter(
+            &ctx.memory.allocator,
+            BufferUsage {
+                transfer_src: true,
+                transfer_dst: true,
+                uniform_buffer: true,
+                storage_buffer: true,
+                ..Default::default()
+            },
+            true,
+            (0..count).map(|_| 0u8),
+        )?;
 
-            return IoFragment {
-                input: Io::Image(input_img),
-                output: Io::Buffer(output_buffer),
-                label: "Output".to_string(),
-            };
-        }
-        IoFragment::none()
+        // build command buffer
+        builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            input_img.clone(),
+            output_buffer.clone(),
+        ))?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Buffer(output_buffer),
+            label: "Output".to_string(),
+        })
+    }
+
+    fn output_info(&self, _input: Option<ImageInfo>) -> Option<ImageInfo> {
+        // the output is a buffer
+        None
     }
 }
 
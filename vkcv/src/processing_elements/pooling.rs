@@ -1,10 +1,16 @@
+use serde::{Deserialize, Serialize};
 use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
     image::{view::ImageView, ImageAccess},
-    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    pipeline::{Pipeline, PipelineBindPoint},
 };
 
-use crate::{utils, vk_init::VkContext};
+use crate::{
+    error::VkcvError,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
 
 use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
 
@@ -15,6 +21,8 @@ mod cs {
     }
 }
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Operation {
     Min,
     Max,
@@ -36,31 +44,25 @@ impl ProcessingElement for Pooling {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
+    ) -> Result<IoFragment, VkcvError> {
         let local_size = 16;
 
-        let pipeline = {
-            let shader = cs::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs::SpecializationConstants {
-                    constant_0: local_size,
-                    constant_1: local_size,
-                    min_max: match self.op {
-                        Operation::Min => 0,
-                        Operation::Max => 1,
-                    },
-                    ..Default::default()
+        let pipeline = utils::compute_pipeline(
+            ctx,
+            cs::load,
+            &cs::SpecializationConstants {
+                constant_0: local_size,
+                constant_1: local_size,
+                min_max: match self.op {
+                    Operation::Min => 0,
+                    Operation::Max => 1,
                 },
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+                ..Default::default()
+            },
+        )?;
 
         // input image
-        let input_img = input.output_image().unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
 
         // output image
         let output_size = (
@@ -69,17 +71,18 @@ impl ProcessingElement for Pooling {
         );
         let output_img = utils::create_storage_image(
             ctx,
+            builder,
             &utils::ImageInfo {
                 width: output_size.0,
                 height: output_size.1,
                 format: input_img.format(),
             },
-        );
+        )?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = PersistentDescriptorSet::new(
             &ctx.memory.descriptor_set_allocator,
@@ -88,8 +91,7 @@ impl ProcessingElement for Pooling {
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
             ],
-        )
-        .unwrap();
+        )?;
 
         builder
             .bind_pipeline_compute(pipeline.clone())
@@ -103,13 +105,24 @@ impl ProcessingElement for Pooling {
             .dispatch(utils::workgroups(
                 &input_img.dimensions().width_height(),
                 &[local_size, local_size],
-            ))
-            .unwrap();
+            ))?;
 
-        IoFragment {
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label: utils::basic_label("Pooling", &output_img),
-        }
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            width: info.width / 2,
+            height: info.height / 2,
+            ..info
+        })
     }
 }
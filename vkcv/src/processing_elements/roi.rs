@@ -0,0 +1,259 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+    sampler::{BorderColor, Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+};
+
+use crate::{
+    error::VkcvError,
+    params::{Param, Parameters},
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
+
+mod cs_r8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/roi.comp.glsl",
+        define: [("FORMAT", "r8")],
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod cs_rgba8 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/roi.comp.glsl",
+        define: [("FORMAT", "rgba8")],
+    }
+}
+
+const FORMATS: [Format; 2] = [Format::R8_UNORM, Format::R8G8B8A8_UNORM];
+
+// a window in normalized coordinates of the frame
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Window {
+    pub offset: [f32; 2],
+    pub extent: [f32; 2],
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl Window {
+    pub fn full() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            extent: [1.0, 1.0],
+        }
+    }
+
+    // centered on `center`, shifted to stay inside of the frame
+    pub fn around(center: [f32; 2], extent: [f32; 2]) -> Self {
+        let offset =
+            [0, 1].map(|i| (center[i] - 0.5 * extent[i]).clamp(0.0, (1.0 - extent[i]).max(0.0)));
+
+        Self { offset, extent }
+    }
+
+    pub fn is_full(&self) -> bool {
+        *self == Self::full()
+    }
+
+    // maps normalized coordinates of the window (e.g. tracker::centroid) to the frame
+    pub fn to_frame(&self, p: [f32; 2]) -> [f32; 2] {
+        [0, 1].map(|i| self.offset[i] + p[i] * self.extent[i])
+    }
+
+    // the window of the next frame, around the target or the full frame once it is lost
+    pub fn follow(&self, target: Option<[f32; 2]>, extent: [f32; 2]) -> Self {
+        match target {
+            Some(p) => Self::around(self.to_frame(p), extent),
+            None => Self::full(),
+        }
+    }
+}
+
+impl Parameters for Window {
+    fn encode(&self) -> Vec<u8> {
+        let params = cs_r8::ty::Params {
+            offset: self.offset,
+            extent: self.extent,
+        };
+
+        bytemuck::bytes_of(&params).to_vec()
+    }
+}
+
+// Resamples a window of the input to a fixed size image.
+//
+// The window can be moved every frame through `params`, thus the subsequent
+// elements only process the region around the target. The full frame is
+// scaled down to the same size while searching for the target. Pixels
+// outside of the frame are 0.
+pub struct Roi {
+    width: u32,
+    height: u32,
+    window: Param<Window>,
+}
+
+impl Roi {
+    // starts with the full frame
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_window(width, height, Window::full())
+    }
+
+    pub fn with_window(width: u32, height: u32, window: Window) -> Self {
+        Self {
+            width,
+            height,
+            window: Param::new(window),
+        }
+    }
+
+    // the window can be changed after the pipeline has been built
+    pub fn params(&self) -> Param<Window> {
+        self.window.clone()
+    }
+
+    // the extent of a window copied 1:1 from a frame of the given size
+    pub fn native_extent(&self, frame: [u32; 2]) -> [f32; 2] {
+        [
+            (self.width as f32 / frame[0] as f32).min(1.0),
+            (self.height as f32 / frame[1] as f32).min(1.0),
+        ]
+    }
+}
+
+impl ProcessingElement for Roi {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+        let format = input_img.format();
+
+        let pipeline = match format {
+            Format::R8_UNORM => {
+                utils::compute_pipeline(ctx, cs_r8::load, &cs_r8::SpecializationConstants {})?
+            }
+            Format::R8G8B8A8_UNORM => {
+                utils::compute_pipeline(ctx, cs_rgba8::load, &cs_rgba8::SpecializationConstants {})?
+            }
+            _ => return Err(VkcvError::UnsupportedFormat(format)),
+        };
+
+        // output image
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                width: self.width,
+                height: self.height,
+                format,
+            },
+        )?;
+
+        // linear filtering when scaling down, nothing outside of the frame
+        let sampler = Sampler::new(
+            ctx.device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::ClampToBorder; 3],
+                border_color: BorderColor::FloatTransparentBlack,
+                ..Default::default()
+            },
+        )?;
+
+        // setup layout
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(0, input_img_view, sampler),
+                WriteDescriptorSet::image_view(1, output_img_view),
+                WriteDescriptorSet::buffer(2, self.window.bind(ctx, builder)?),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(
+                &output_img.dimensions().width_height(),
+                &[16, 16],
+            ))?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("Roi", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &FORMATS
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            width: self.width,
+            height: self.height,
+            format: info.format,
+        })
+    }
+
+    fn handle(&self) -> Option<Box<dyn Any>> {
+        Some(Box::new(self.params()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn follow() {
+        let extent = [0.25, 0.5];
+
+        // stays inside of the frame
+        let window = Window::full().follow(Some([0.95, 0.1]), extent);
+        assert_eq!(window.offset, [0.75, 0.0]);
+        assert_eq!(window.extent, extent);
+
+        // window coordinates are relative to the previous window
+        let next = window.follow(Some([0.5, 0.5]), extent);
+        assert_eq!(next.to_frame([0.5, 0.5]), window.to_frame([0.5, 0.5]));
+
+        assert!(next.follow(None, extent).is_full());
+    }
+}
@@ -0,0 +1,142 @@
+use vulkano::{
+    descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+    format::Format,
+    image::{view::ImageView, ImageAccess},
+    pipeline::{Pipeline, PipelineBindPoint},
+};
+
+use crate::{
+    error::VkcvError,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+use super::{AutoCommandBufferBuilder, Io, IoFragment, ProcessingElement};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/sobel.comp.glsl",
+    }
+}
+
+// 3x3 sobel operator on a grayscale image
+//
+// The result is a rgba16f image holding the signed gradient (gx, gy), each
+// in [-4, 4], its magnitude and its orientation in radians [-pi, pi].
+pub struct Sobel {}
+
+impl Default for Sobel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sobel {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl ProcessingElement for Sobel {
+    fn build(
+        &self,
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input: &IoFragment,
+    ) -> Result<IoFragment, VkcvError> {
+        let local_size = 16;
+
+        let pipeline = utils::compute_pipeline(
+            ctx,
+            cs::load,
+            &cs::SpecializationConstants {
+                constant_0: local_size,
+                constant_1: local_size,
+            },
+        )?;
+
+        // input image
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
+
+        // output image
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo::from_image(&input_img, Format::R16G16B16A16_SFLOAT),
+        )?;
+
+        // setup layout
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
+
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, input_img_view),
+                WriteDescriptorSet::image_view(1, output_img_view),
+            ],
+        )?;
+
+        // build command buffer
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(
+                &input_img.dimensions().width_height(),
+                &[local_size, local_size],
+            ))?;
+
+        Ok(IoFragment {
+            input: Io::Image(input_img),
+            output: Io::Image(output_img.clone()),
+            label: utils::basic_label("Sobel", &output_img),
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        input.map(|info| ImageInfo {
+            format: Format::R16G16B16A16_SFLOAT,
+            ..info
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn output() {
+        let sobel = Sobel::new();
+        let info = ImageInfo {
+            width: 2,
+            height: 1,
+            format: Format::R8_UNORM,
+        };
+        let out = sobel.output_info(Some(info)).unwrap();
+        assert_eq!(out.format, Format::R16G16B16A16_SFLOAT);
+        assert_eq!((out.width, out.height), (2, 1));
+
+        // (gx, gy, mag, orient) of a horizontal and a vertical edge
+        let pixels = [[4.0, 0.0, 4.0, 0.0], [0.0, -4.0, 4.0, -1.57]];
+        let data: Vec<u8> = pixels
+            .iter()
+            .flatten()
+            .flat_map(|v| half::f16::from_f32(*v).to_le_bytes())
+            .collect();
+        let (_, rgba) = utils::image_to_rgba8(&out, &data);
+        assert_eq!(rgba, vec![255, 127, 255, 255, 127, 0, 255, 255]);
+    }
+}
@@ -1,15 +1,17 @@
 use half::f16;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use vulkano::{
     descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
     format::Format,
     image::{view::ImageView, ImageAccess, StorageImage},
-    pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
+    pipeline::{Pipeline, PipelineBindPoint},
     sampler::{Sampler, SamplerCreateInfo},
 };
 
 use crate::{
     endpoints::image_download::TransferredImage,
+    error::VkcvError,
     utils::{self, ImageInfo},
     vk_init::VkContext,
 };
@@ -32,11 +34,52 @@ mod cs_cm {
     }
 }
 
+// optional: second-order central moments mask, about the reduced centroid
+mod cs_moments {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/tracker_moments.comp.glsl",
+    }
+}
+
+// optional: bounding box, first 2x2 reduction straight from the mask
+mod cs_bbox_mask {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/tracker_bbox_mask.comp.glsl",
+    }
+}
+
+// optional: bounding box, subsequent 2x2 min/max reductions
+mod cs_bbox_pool {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/tracker_bbox_pool.comp.glsl",
+    }
+}
+
+// optional: pack centroid, moments and bounding box into a 3x1 image
+mod cs_pack {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/tracker_pack.comp.glsl",
+    }
+}
+
 // subsequent passes: scale down 2x
 mod cs_pool2 {
     vulkano_shaders::shader! {
         ty: "compute",
         path: "src/shaders/mean_pooling_2.comp.glsl",
+        define: [("FORMAT", "rgba16f")],
+    }
+}
+
+mod cs_pool2_f32 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/mean_pooling_2.comp.glsl",
+        define: [("FORMAT", "rgba32f")],
     }
 }
 
@@ -52,6 +95,15 @@ mod cs_pool4 {
     vulkano_shaders::shader! {
         ty: "compute",
         path: "src/shaders/mean_pooling_4.comp.glsl",
+        define: [("FORMAT", "rgba16f")],
+    }
+}
+
+mod cs_pool4_f32 {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/mean_pooling_4.comp.glsl",
+        define: [("FORMAT", "rgba32f")],
     }
 }
 
@@ -62,7 +114,8 @@ mod cs_pool4_sampler {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PoolingStrategy {
     Pooling4,
     Pooling2,
@@ -70,7 +123,8 @@ pub enum PoolingStrategy {
     SampledPooling2,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Canvas {
     Pad,
     Crop,
@@ -79,11 +133,26 @@ pub enum Canvas {
 pub struct Tracker {
     pooling: PoolingStrategy,
     canvas: Canvas,
+    extent: bool,
 }
 
 impl Tracker {
     pub fn new(pooling: PoolingStrategy, canvas: Canvas) -> Self {
-        Self { pooling, canvas }
+        Self {
+            pooling,
+            canvas,
+            extent: false,
+        }
+    }
+
+    // additionally reduces the bounding box and the second-order moments,
+    // the output becomes a 3x1 rgba32f image, see extent
+    pub fn with_extent(pooling: PoolingStrategy, canvas: Canvas) -> Self {
+        Self {
+            pooling,
+            canvas,
+            extent: true,
+        }
     }
 
     fn canvas(
@@ -91,20 +160,14 @@ impl Tracker {
         builder: &mut AutoCommandBufferBuilder,
         input_img: Arc<StorageImage>,
         canvas: Canvas,
-    ) -> Arc<StorageImage> {
-        let pipeline = {
-            let shader = cs_canvas::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs_canvas::SpecializationConstants {
-                    ..Default::default()
-                },
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+    ) -> Result<Arc<StorageImage>, VkcvError> {
+        let pipeline = utils::compute_pipeline(
+            ctx,
+            cs_canvas::load,
+            &cs_canvas::SpecializationConstants {
+                ..Default::default()
+            },
+        )?;
 
         // find closest power of two size
         let stride = input_img
@@ -119,23 +182,24 @@ impl Tracker {
 
         // skip this pass if image is already power of two
         if input_img.dimensions().width_height() == [pot, pot] {
-            return input_img;
+            return Ok(input_img);
         }
 
         // output image
         let output_img = utils::create_storage_image(
             ctx,
+            builder,
             &ImageInfo {
                 format: Format::R8_UNORM,
                 height: pot,
                 width: pot,
             },
-        );
+        )?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = PersistentDescriptorSet::new(
             &ctx.memory.descriptor_set_allocator,
@@ -144,8 +208,7 @@ impl Tracker {
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
             ],
-        )
-        .unwrap();
+        )?;
 
         let workgroups = utils::workgroups(&output_img.dimensions().width_height(), &[16, 16]);
 
@@ -158,10 +221,9 @@ impl Tracker {
                 0,
                 set,
             )
-            .dispatch(workgroups)
-            .unwrap();
+            .dispatch(workgroups)?;
 
-        output_img
+        Ok(output_img)
     }
 
     fn coordinate_mask(
@@ -169,35 +231,30 @@ impl Tracker {
         builder: &mut AutoCommandBufferBuilder,
         input_img: Arc<StorageImage>,
         sub_dims: &[u32; 2],
-    ) -> Arc<StorageImage> {
+    ) -> Result<Arc<StorageImage>, VkcvError> {
         // ref: https://developer.nvidia.com/gpugems/gpugems3/part-iv-image-effects/chapter-26-object-detection-color-using-gpu-real-time-video
         // pipeline
-        let pipeline = {
-            let shader = cs_cm::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
-                &cs_cm::SpecializationConstants {
-                    inv_width: 1.0 / sub_dims[0] as f32,
-                    inv_height: 1.0 / sub_dims[1] as f32,
-                    ..Default::default()
-                },
-                None,
-                |_| {},
-            )
-            .unwrap()
-        };
+        let pipeline = utils::compute_pipeline(
+            ctx,
+            cs_cm::load,
+            &cs_cm::SpecializationConstants {
+                inv_width: 1.0 / sub_dims[0] as f32,
+                inv_height: 1.0 / sub_dims[1] as f32,
+                ..Default::default()
+            },
+        )?;
 
         // output image
         let output_img = utils::create_storage_image(
             ctx,
+            builder,
             &ImageInfo::from_image(&input_img, Format::R16G16B16A16_SFLOAT),
-        );
+        )?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = PersistentDescriptorSet::new(
             &ctx.memory.descriptor_set_allocator,
@@ -206,8 +263,7 @@ impl Tracker {
                 WriteDescriptorSet::image_view(0, input_img_view),
                 WriteDescriptorSet::image_view(1, output_img_view),
             ],
-        )
-        .unwrap();
+        )?;
 
         let workgroups = utils::workgroups(&output_img.dimensions().width_height(), &[16, 16]);
 
@@ -220,10 +276,212 @@ impl Tracker {
                 0,
                 set.clone(),
             )
-            .dispatch(workgroups)
-            .unwrap();
+            .dispatch(workgroups)?;
+
+        Ok(output_img)
+    }
+
+    // rgba32f, the moments of small blobs are below the precision of rgba16f
+    fn moments(
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input_img: Arc<StorageImage>,
+        centroid_img: Arc<StorageImage>,
+        sub_dims: &[u32; 2],
+    ) -> Result<Arc<StorageImage>, VkcvError> {
+        let pipeline = utils::compute_pipeline(
+            ctx,
+            cs_moments::load,
+            &cs_moments::SpecializationConstants {
+                inv_width: 1.0 / sub_dims[0] as f32,
+                inv_height: 1.0 / sub_dims[1] as f32,
+                ..Default::default()
+            },
+        )?;
+
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo::from_image(&input_img, Format::R32G32B32A32_SFLOAT),
+        )?;
+
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, ImageView::new_default(input_img)?),
+                WriteDescriptorSet::image_view(1, ImageView::new_default(centroid_img)?),
+                WriteDescriptorSet::image_view(2, ImageView::new_default(output_img.clone())?),
+            ],
+        )?;
+
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(
+                &output_img.dimensions().width_height(),
+                &[16, 16],
+            ))?;
+
+        Ok(output_img)
+    }
+
+    fn bounding_box(
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        input_img: Arc<StorageImage>,
+        sub_dims: &[u32; 2],
+    ) -> Result<Arc<StorageImage>, VkcvError> {
+        // first 2x2 reduction reads the mask directly
+        let pipeline = utils::compute_pipeline(
+            ctx,
+            cs_bbox_mask::load,
+            &cs_bbox_mask::SpecializationConstants {
+                inv_width: 1.0 / sub_dims[0] as f32,
+                inv_height: 1.0 / sub_dims[1] as f32,
+            },
+        )?;
+
+        let size = (input_img.dimensions().width() / 2).max(1);
+        let mut output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                format: Format::R32G32B32A32_SFLOAT,
+                height: size,
+                width: size,
+            },
+        )?;
+
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, ImageView::new_default(input_img)?),
+                WriteDescriptorSet::image_view(1, ImageView::new_default(output_img.clone())?),
+            ],
+        )?;
+
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch(utils::workgroups(&[size, size], &[16, 16]))?;
+
+        // min/max down to 1x1 px
+        while output_img.dimensions().width() > 1 {
+            let out_size = output_img.dimensions().width() / 2;
+            let local_size = [out_size.min(16), out_size.min(16)];
+
+            let pipeline = utils::compute_pipeline(
+                ctx,
+                cs_bbox_pool::load,
+                &cs_bbox_pool::SpecializationConstants {
+                    constant_0: local_size[0],
+                    constant_1: local_size[1],
+                },
+            )?;
+
+            let pooled_img = utils::create_storage_image(
+                ctx,
+                builder,
+                &ImageInfo {
+                    format: Format::R32G32B32A32_SFLOAT,
+                    height: out_size,
+                    width: out_size,
+                },
+            )?;
+
+            let layout = pipeline.layout().set_layouts().get(0).unwrap();
+            let set = PersistentDescriptorSet::new(
+                &ctx.memory.descriptor_set_allocator,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view(0, ImageView::new_default(output_img.clone())?),
+                    WriteDescriptorSet::image_view(1, ImageView::new_default(pooled_img.clone())?),
+                ],
+            )?;
+
+            builder
+                .bind_pipeline_compute(pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    pipeline.layout().clone(),
+                    0,
+                    set,
+                )
+                .dispatch(utils::workgroups(&[out_size, out_size], &local_size))?;
+
+            utils::release_storage_image(builder, output_img);
+            output_img = pooled_img;
+        }
+
+        Ok(output_img)
+    }
+
+    fn pack(
+        ctx: &VkContext,
+        builder: &mut AutoCommandBufferBuilder,
+        centroid_img: Arc<StorageImage>,
+        moments_img: Arc<StorageImage>,
+        bbox_img: Arc<StorageImage>,
+    ) -> Result<Arc<StorageImage>, VkcvError> {
+        let pipeline = utils::compute_pipeline(
+            ctx,
+            cs_pack::load,
+            &cs_pack::SpecializationConstants {
+                ..Default::default()
+            },
+        )?;
+
+        let output_img = utils::create_storage_image(
+            ctx,
+            builder,
+            &ImageInfo {
+                format: Format::R32G32B32A32_SFLOAT,
+                height: 1,
+                width: 3,
+            },
+        )?;
+
+        let layout = pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &ctx.memory.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, ImageView::new_default(centroid_img.clone())?),
+                WriteDescriptorSet::image_view(1, ImageView::new_default(moments_img.clone())?),
+                WriteDescriptorSet::image_view(2, ImageView::new_default(bbox_img.clone())?),
+                WriteDescriptorSet::image_view(3, ImageView::new_default(output_img.clone())?),
+            ],
+        )?;
+
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch([1, 1, 1])?;
 
-        output_img
+        utils::release_storage_image(builder, centroid_img);
+        utils::release_storage_image(builder, moments_img);
+        utils::release_storage_image(builder, bbox_img);
+
+        Ok(output_img)
     }
 
     fn pooling(
@@ -231,7 +489,8 @@ impl Tracker {
         builder: &mut AutoCommandBufferBuilder,
         mut input_img: Arc<StorageImage>,
         pooling_strategy: PoolingStrategy,
-    ) -> Arc<StorageImage> {
+        rgba32f: bool,
+    ) -> Result<Arc<StorageImage>, VkcvError> {
         let size = input_img.dimensions().width_height();
         assert_eq!(size[0], size[1]);
 
@@ -239,38 +498,40 @@ impl Tracker {
         let divs_by_4 = (divs_by_2 as f32 / 2.0).floor() as u32;
         let remaining_divs_by_2 = divs_by_2 - (divs_by_4 * 2);
 
+        // linear filtering of rgba32f is not supported by all devices, thus
+        // the sampler is only used for rgba16f
         match pooling_strategy {
             PoolingStrategy::Pooling4 => {
                 for _ in 0..divs_by_4 {
-                    input_img = Self::pooling4(ctx, builder, input_img, false);
+                    input_img = Self::pooling4(ctx, builder, input_img, false, rgba32f)?;
                 }
 
                 for _ in 0..remaining_divs_by_2 {
-                    input_img = Self::pooling2(ctx, builder, input_img, false);
+                    input_img = Self::pooling2(ctx, builder, input_img, false, rgba32f)?;
                 }
             }
             PoolingStrategy::SampledPooling4 => {
                 for _ in 0..divs_by_4 {
-                    input_img = Self::pooling4(ctx, builder, input_img, true);
+                    input_img = Self::pooling4(ctx, builder, input_img, !rgba32f, rgba32f)?;
                 }
 
                 for _ in 0..remaining_divs_by_2 {
-                    input_img = Self::pooling2(ctx, builder, input_img, true);
+                    input_img = Self::pooling2(ctx, builder, input_img, !rgba32f, rgba32f)?;
                 }
             }
             PoolingStrategy::Pooling2 => {
                 for _ in 0..divs_by_2 {
-                    input_img = Self::pooling2(ctx, builder, input_img, false);
+                    input_img = Self::pooling2(ctx, builder, input_img, false, rgba32f)?;
                 }
             }
             PoolingStrategy::SampledPooling2 => {
                 for _ in 0..divs_by_2 {
-                    input_img = Self::pooling2(ctx, builder, input_img, true);
+                    input_img = Self::pooling2(ctx, builder, input_img, !rgba32f, rgba32f)?;
                 }
             }
         }
 
-        input_img
+        Ok(input_img)
     }
 
     fn pooling2(
@@ -278,64 +539,71 @@ impl Tracker {
         builder: &mut AutoCommandBufferBuilder,
         input_img: Arc<StorageImage>,
         use_sampler: bool,
-    ) -> Arc<StorageImage> {
+        rgba32f: bool,
+    ) -> Result<Arc<StorageImage>, VkcvError> {
         let in_size = input_img.dimensions().width();
         let out_size = in_size / 2;
 
         let local_size = [out_size.min(16), out_size.min(16)];
 
         let pipeline = if use_sampler {
-            let shader = cs_pool2_sampler::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
+            utils::compute_pipeline(
+                ctx,
+                cs_pool2_sampler::load,
                 &cs_pool2_sampler::SpecializationConstants {
                     constant_0: local_size[0],
                     constant_1: local_size[1],
                     inv_size: 1.0 / (out_size as f32),
                     ..Default::default()
                 },
-                None,
-                |_| {},
-            )
-            .unwrap()
+            )?
+        } else if rgba32f {
+            utils::compute_pipeline(
+                ctx,
+                cs_pool2_f32::load,
+                &cs_pool2_f32::SpecializationConstants {
+                    constant_0: local_size[0],
+                    constant_1: local_size[1],
+                    ..Default::default()
+                },
+            )?
         } else {
-            let shader = cs_pool2::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
+            utils::compute_pipeline(
+                ctx,
+                cs_pool2::load,
                 &cs_pool2::SpecializationConstants {
                     constant_0: local_size[0],
                     constant_1: local_size[1],
                     ..Default::default()
                 },
-                None,
-                |_| {},
-            )
-            .unwrap()
+            )?
         };
 
         // output image
         let output_img = utils::create_storage_image(
             ctx,
+            builder,
             &ImageInfo {
-                format: Format::R16G16B16A16_SFLOAT,
+                format: if rgba32f {
+                    Format::R32G32B32A32_SFLOAT
+                } else {
+                    Format::R16G16B16A16_SFLOAT
+                },
                 height: out_size,
                 width: out_size,
             },
-        );
+        )?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = if use_sampler {
             let sampler = Sampler::new(
                 ctx.device.clone(),
                 SamplerCreateInfo::simple_repeat_linear_no_mipmap(),
-            )
-            .unwrap();
+            )?;
             PersistentDescriptorSet::new(
                 &ctx.memory.descriptor_set_allocator,
                 layout.clone(),
@@ -343,8 +611,7 @@ impl Tracker {
                     WriteDescriptorSet::image_view_sampler(0, input_img_view, sampler),
                     WriteDescriptorSet::image_view(1, output_img_view),
                 ],
-            )
-            .unwrap()
+            )?
         } else {
             PersistentDescriptorSet::new(
                 &ctx.memory.descriptor_set_allocator,
@@ -353,8 +620,7 @@ impl Tracker {
                     WriteDescriptorSet::image_view(0, input_img_view),
                     WriteDescriptorSet::image_view(1, output_img_view),
                 ],
-            )
-            .unwrap()
+            )?
         };
 
         // workgroups
@@ -369,10 +635,12 @@ impl Tracker {
                 0,
                 set.clone(),
             )
-            .dispatch(workgroups)
-            .unwrap();
+            .dispatch(workgroups)?;
 
-        output_img
+        // the previous level is not read anymore
+        utils::release_storage_image(builder, input_img);
+
+        Ok(output_img)
     }
 
     fn pooling4(
@@ -380,64 +648,71 @@ impl Tracker {
         builder: &mut AutoCommandBufferBuilder,
         input_img: Arc<StorageImage>,
         use_sampler: bool,
-    ) -> Arc<StorageImage> {
+        rgba32f: bool,
+    ) -> Result<Arc<StorageImage>, VkcvError> {
         let in_size = input_img.dimensions().width();
         let out_size = in_size / 4;
 
         let local_size = [out_size.min(16), out_size.min(16)];
 
         let pipeline = if use_sampler {
-            let shader = cs_pool4_sampler::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
+            utils::compute_pipeline(
+                ctx,
+                cs_pool4_sampler::load,
                 &cs_pool4_sampler::SpecializationConstants {
                     constant_0: local_size[0],
                     constant_1: local_size[1],
                     inv_size: 1.0 / (out_size as f32),
                     ..Default::default()
                 },
-                None,
-                |_| {},
-            )
-            .unwrap()
+            )?
+        } else if rgba32f {
+            utils::compute_pipeline(
+                ctx,
+                cs_pool4_f32::load,
+                &cs_pool4_f32::SpecializationConstants {
+                    constant_0: local_size[0],
+                    constant_1: local_size[1],
+                    ..Default::default()
+                },
+            )?
         } else {
-            let shader = cs_pool4::load(ctx.device.clone()).unwrap();
-            ComputePipeline::new(
-                ctx.device.clone(),
-                shader.entry_point("main").unwrap(),
+            utils::compute_pipeline(
+                ctx,
+                cs_pool4::load,
                 &cs_pool4::SpecializationConstants {
                     constant_0: local_size[0],
                     constant_1: local_size[1],
                     ..Default::default()
                 },
-                None,
-                |_| {},
-            )
-            .unwrap()
+            )?
         };
 
         // output image
         let output_img = utils::create_storage_image(
             ctx,
+            builder,
             &ImageInfo {
-                format: Format::R16G16B16A16_SFLOAT,
+                format: if rgba32f {
+                    Format::R32G32B32A32_SFLOAT
+                } else {
+                    Format::R16G16B16A16_SFLOAT
+                },
                 height: out_size,
                 width: out_size,
             },
-        );
+        )?;
 
         // setup layout
         let layout = pipeline.layout().set_layouts().get(0).unwrap();
-        let input_img_view = ImageView::new_default(input_img.clone()).unwrap();
-        let output_img_view = ImageView::new_default(output_img.clone()).unwrap();
+        let input_img_view = ImageView::new_default(input_img.clone())?;
+        let output_img_view = ImageView::new_default(output_img.clone())?;
 
         let set = if use_sampler {
             let sampler = Sampler::new(
                 ctx.device.clone(),
                 SamplerCreateInfo::simple_repeat_linear_no_mipmap(),
-            )
-            .unwrap();
+            )?;
             PersistentDescriptorSet::new(
                 &ctx.memory.descriptor_set_allocator,
                 layout.clone(),
@@ -445,8 +720,7 @@ impl Tracker {
                     WriteDescriptorSet::image_view_sampler(0, input_img_view, sampler),
                     WriteDescriptorSet::image_view(1, output_img_view),
                 ],
-            )
-            .unwrap()
+            )?
         } else {
             PersistentDescriptorSet::new(
                 &ctx.memory.descriptor_set_allocator,
@@ -455,8 +729,7 @@ impl Tracker {
                     WriteDescriptorSet::image_view(0, input_img_view),
                     WriteDescriptorSet::image_view(1, output_img_view),
                 ],
-            )
-            .unwrap()
+            )?
         };
 
         // workgroups
@@ -471,10 +744,12 @@ impl Tracker {
                 0,
                 set.clone(),
             )
-            .dispatch(workgroups)
-            .unwrap();
+            .dispatch(workgroups)?;
 
-        output_img
+        // the previous level is not read anymore
+        utils::release_storage_image(builder, input_img);
+
+        Ok(output_img)
     }
 }
 
@@ -484,13 +759,13 @@ impl ProcessingElement for Tracker {
         ctx: &VkContext,
         builder: &mut AutoCommandBufferBuilder,
         input: &IoFragment,
-    ) -> IoFragment {
+    ) -> Result<IoFragment, VkcvError> {
         // input image
-        let input_img = input.output_image().unwrap();
+        let input_img = input.output_image().ok_or(VkcvError::MissingImage)?;
 
         // canvas the input image to be a power of two
         // this is skipped if the input image is already a POT
-        let output_img_canvas = Self::canvas(ctx, builder, input_img.clone(), self.canvas);
+        let output_img_canvas = Self::canvas(ctx, builder, input_img.clone(), self.canvas)?;
 
         // coordinate mask
         let output_img_cm = Self::coordinate_mask(
@@ -498,13 +773,38 @@ impl ProcessingElement for Tracker {
             builder,
             output_img_canvas.clone(),
             &input_img.dimensions().width_height(),
-        );
+        )?;
 
         // scale down to 1x1 px
-        let output_img = Self::pooling(ctx, builder, output_img_cm.clone(), self.pooling);
+        let mut output_img =
+            Self::pooling(ctx, builder, output_img_cm.clone(), self.pooling, false)?;
+
+        // second-order moments about the centroid and bounding box
+        let extent_imgs = if self.extent {
+            let moments_img = Self::moments(
+                ctx,
+                builder,
+                output_img_canvas.clone(),
+                output_img.clone(),
+                &input_img.dimensions().width_height(),
+            )?;
+            let bbox_img = Self::bounding_box(
+                ctx,
+                builder,
+                output_img_canvas.clone(),
+                &input_img.dimensions().width_height(),
+            )?;
+            Some((moments_img, bbox_img))
+        } else {
+            None
+        };
+
+        if !Arc::ptr_eq(&output_img_canvas, &input_img) {
+            utils::release_storage_image(builder, output_img_canvas.clone());
+        }
 
         // create a descriptive label
-        let label = format!(
+        let mut label = format!(
             "Tracker\n\t- {}\n\t- {}\n\t- {}\n\t- {}\n\t",
             utils::basic_label("Input", &input_img),
             utils::basic_label("Canvas", &output_img_canvas),
@@ -512,21 +812,122 @@ impl ProcessingElement for Tracker {
             utils::basic_label("Downscale", &output_img),
         );
 
-        IoFragment {
+        if let Some((moments_img, bbox_img)) = extent_imgs {
+            let moments_img = Self::pooling(ctx, builder, moments_img, self.pooling, true)?;
+            output_img = Self::pack(ctx, builder, output_img, moments_img, bbox_img)?;
+
+            label += &format!("- {}\n\t", utils::basic_label("Extent", &output_img));
+        }
+
+        Ok(IoFragment {
             input: Io::Image(input_img),
             output: Io::Image(output_img.clone()),
             label,
+        })
+    }
+
+    fn input_formats(&self) -> &[Format] {
+        &[Format::R8_UNORM]
+    }
+
+    fn output_info(&self, input: Option<ImageInfo>) -> Option<ImageInfo> {
+        // pooled down to a single pixel, or packed with the extent
+        input.map(|_| {
+            if self.extent {
+                ImageInfo {
+                    width: 3,
+                    height: 1,
+                    format: Format::R32G32B32A32_SFLOAT,
+                }
+            } else {
+                ImageInfo {
+                    width: 1,
+                    height: 1,
+                    format: Format::R16G16B16A16_SFLOAT,
+                }
+            }
+        })
+    }
+}
+
+// spatial extent of the mask in normalized coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Extent {
+    pub centroid: [f32; 2],
+    pub area: f32,
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    // second-order central moments (normalized by area)
+    pub mu20: f32,
+    pub mu02: f32,
+    pub mu11: f32,
+}
+
+impl Extent {
+    // the moments are taken about the centroid, see tracker_moments.comp.glsl
+    fn from_pixels(sums: [f32; 4], moments: [f32; 4], bbox: [f32; 4]) -> Option<Self> {
+        let area = sums[2];
+        if area <= 0.0 || moments[3] <= 0.0 {
+            return None;
         }
+
+        Some(Self {
+            centroid: [sums[0] / area, sums[1] / area],
+            area,
+            min: [bbox[0], bbox[1]],
+            max: [bbox[2], bbox[3]],
+            // the rgba32f area is more precise than the rgba16f one
+            mu20: moments[0] / moments[3],
+            mu02: moments[1] / moments[3],
+            mu11: moments[2] / moments[3],
+        })
+    }
+
+    // orientation of the major axis
+    pub fn orientation(&self) -> f32 {
+        0.5 * (2.0 * self.mu11).atan2(self.mu20 - self.mu02)
     }
 }
 
-pub fn centroid(tf_img: &TransferredImage) -> ([f32; 2], f32) {
-    assert_eq!(tf_img.info().width, 1);
-    assert_eq!(tf_img.info().height, 1);
+// decodes the output of Tracker::with_extent, None if the mask is empty
+pub fn extent(tf_img: &TransferredImage) -> Result<Option<Extent>, VkcvError> {
+    let info = tf_img.info();
+    if info.format != Format::R32G32B32A32_SFLOAT {
+        return Err(VkcvError::UnsupportedFormat(info.format));
+    }
+    if info.width != 3 || info.height != 1 {
+        return Err(VkcvError::InvalidParameters(format!(
+            "expected the 3x1 output of a tracker with extent, got {}x{}",
+            info.width, info.height
+        )));
+    }
 
     let buffer = tf_img.buffer_content();
+    let pixel = |i: usize| {
+        let mut p = [0.0; 4];
+        for (c, v) in p.iter_mut().enumerate() {
+            let o = i * 16 + c * 4;
+            *v = f32::from_le_bytes([buffer[o], buffer[o + 1], buffer[o + 2], buffer[o + 3]]);
+        }
+        p
+    };
+
+    Ok(Extent::from_pixels(pixel(0), pixel(1), pixel(2)))
+}
 
-    match tf_img.info().format {
+pub fn centroid(tf_img: &TransferredImage) -> Result<([f32; 2], f32), VkcvError> {
+    // the extent is packed after the centroid
+    let info = tf_img.info();
+    if !(info.width == 1 || info.width == 3) || info.height != 1 {
+        return Err(VkcvError::InvalidParameters(format!(
+            "expected the 1x1 resp. 3x1 output of a tracker, got {}x{}",
+            info.width, info.height
+        )));
+    }
+
+    let buffer = tf_img.buffer_content();
+
+    Ok(match info.format {
         Format::R16G16B16A16_SFLOAT => {
             let x = f16::from_le_bytes([buffer[0], buffer[1]]).to_f32();
             let y = f16::from_le_bytes([buffer[2], buffer[3]]).to_f32();
@@ -540,12 +941,128 @@ pub fn centroid(tf_img: &TransferredImage) -> ([f32; 2], f32) {
             let z = f32::from_le_bytes([buffer[8], buffer[9], buffer[10], buffer[11]]);
             ([x / z, y / z], z)
         }
-        _ => ([f32::NAN, f32::NAN], f32::NAN),
-    }
+        format => return Err(VkcvError::UnsupportedFormat(format)),
+    })
 }
 
 #[cfg(test)]
 mod test {
+    use super::Extent;
+
+    #[test]
+    fn extent() {
+        // uniform square [0.2, 0.4] covering a quarter of the area
+        let (lo, hi): (f32, f32) = (0.2, 0.4);
+        let mean = (lo + hi) / 2.0;
+        let var = (hi - lo).powi(2) / 12.0;
+        let area = 0.25;
+
+        let e = Extent::from_pixels(
+            [mean * area, mean * area, area, area],
+            [var * area, var * area, 0.0, area],
+            [lo, lo, hi, hi],
+        )
+        .unwrap();
+
+        assert!((e.centroid[0] - mean).abs() < 1e-6);
+        assert!((e.mu20 - var).abs() < 1e-6);
+        assert!((e.mu02 - var).abs() < 1e-6);
+        assert!(e.mu11.abs() < 1e-6);
+        assert_eq!(e.min, [lo, lo]);
+        assert_eq!(e.max, [hi, hi]);
+
+        assert!(Extent::from_pixels([0.0; 4], [0.0; 4], [1.0, 1.0, 0.0, 0.0]).is_none());
+    }
+
+    // emulates mean_pooling_4.comp.glsl, `store` rounds to the image format
+    fn reduce(mut img: Vec<[f32; 4]>, mut size: usize, store: fn(f32) -> f32) -> [f32; 4] {
+        while size > 1 {
+            let out_size = size / 4;
+            img = (0..out_size * out_size)
+                .map(|i| {
+                    let (x, y) = (i % out_size * 4, i / out_size * 4);
+                    let mut d = [0.0; 4];
+                    for j in 0..16 {
+                        let v = img[(y + j / 4) * size + x + j % 4];
+                        for c in 0..4 {
+                            d[c] += v[c];
+                        }
+                    }
+                    d.map(|v| store(v * 0.0625))
+                })
+                .collect();
+            size = out_size;
+        }
+        img[0]
+    }
+
+    #[test]
+    fn small_blob() {
+        // ellipse with semi-axes of 6 and 2 px rotated by 0.5 rad
+        let size = 256;
+        let angle: f32 = 0.5;
+        let mask: Vec<f32> = (0..size * size)
+            .map(|i| {
+                let (x, y) = ((i % size) as f32 - 100.0, (i / size) as f32 - 60.0);
+                let u = x * angle.cos() + y * angle.sin();
+                let v = y * angle.cos() - x * angle.sin();
+                ((u / 6.0).powi(2) + (v / 2.0).powi(2) <= 1.0) as u8 as f32
+            })
+            .collect();
+        let pos = |i: usize| {
+            [
+                (i % size) as f32 / size as f32,
+                (i / size) as f32 / size as f32,
+            ]
+        };
+
+        // tracker_cm.comp.glsl and its rgba16f reduction
+        let f16 = |v: f32| half::f16::from_f32(v).to_f32();
+        let cm = (0..size * size)
+            .map(|i| {
+                let (r, p) = (mask[i], pos(i));
+                [r * p[0], r * p[1], r, r].map(f16)
+            })
+            .collect();
+        let sums = reduce(cm, size, f16);
+
+        // tracker_moments.comp.glsl and its rgba32f reduction
+        let c = [sums[0] / sums[2], sums[1] / sums[2]];
+        let moments = (0..size * size)
+            .map(|i| {
+                let (r, p) = (mask[i], pos(i));
+                let d = [p[0] - c[0], p[1] - c[1]];
+                [r * d[0] * d[0], r * d[1] * d[1], r * d[0] * d[1], r]
+            })
+            .collect();
+        let moments = reduce(moments, size, |v| v);
+
+        let e = Extent::from_pixels(sums, moments, [0.0, 0.0, 1.0, 1.0]).unwrap();
+
+        // exact moments of the mask
+        let n = mask.iter().sum::<f32>() as f64;
+        let mean = |f: &dyn Fn([f64; 2]) -> f64| {
+            (0..size * size)
+                .filter(|i| mask[*i] > 0.0)
+                .map(|i| f(pos(i).map(|v| v as f64)))
+                .sum::<f64>()
+                / n
+        };
+        let (cx, cy) = (mean(&|p| p[0]), mean(&|p| p[1]));
+        let mu20 = mean(&|p| (p[0] - cx).powi(2));
+        let mu02 = mean(&|p| (p[1] - cy).powi(2));
+        let mu11 = mean(&|p| (p[0] - cx) * (p[1] - cy));
+        let orientation = 0.5 * (2.0 * mu11).atan2(mu20 - mu02);
+
+        assert!((e.centroid[0] as f64 - cx).abs() < 1e-3);
+        assert!((e.centroid[1] as f64 - cy).abs() < 1e-3);
+        assert!((e.mu20 as f64 - mu20).abs() < 0.01 * mu20);
+        assert!((e.mu02 as f64 - mu02).abs() < 0.01 * mu20);
+        assert!((e.mu11 as f64 - mu11).abs() < 0.01 * mu20);
+        assert!((e.orientation() as f64 - orientation).abs() < 0.01);
+        assert!((orientation - angle as f64).abs() < 0.1);
+    }
+
     #[test]
     fn reduce_4x() {
         let size = 1024;
@@ -554,8 +1071,8 @@ mod test {
         let divs_by_4 = (a / 2.0).floor() as u32;
         let divs_by_2 = a as u32 - (divs_by_4 * 2);
 
-        dbg!(size);
-        dbg!(divs_by_4);
-        dbg!(divs_by_2);
+        // 1024 -> 256 -> 64 -> 16 -> 4 -> 1
+        assert_eq!(divs_by_4, 5);
+        assert_eq!(divs_by_2, 0);
     }
 }
@@ -0,0 +1,170 @@
+use std::{fmt, io::Write, sync::Arc};
+
+use vulkano::{
+    query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType},
+    sync::PipelineStage,
+};
+
+use crate::{
+    error::VkcvError, processing_elements::AutoCommandBufferBuilder, utils::ImageInfo,
+    vk_init::VkContext,
+};
+
+pub const CSV_HEADER: &str = "frame,stage,label,us,width,height";
+
+// Timestamps written before and after the commands of each element.
+//
+// The queries live in the command buffer of the pipeline itself, hence the
+// durations do not include any submission overhead. Both timestamps are
+// written at the bottom of the pipe, i.e. once all previously recorded
+// commands are finished.
+#[derive(Clone)]
+pub struct Profiler {
+    pool: Arc<QueryPool>,
+    // nanoseconds per tick
+    period: f32,
+    valid_bits: u32,
+    stages: Vec<(String, Option<ImageInfo>)>,
+}
+
+impl Profiler {
+    pub(crate) fn new(ctx: &VkContext, stages: usize) -> Result<Self, VkcvError> {
+        let physical_device = ctx.device.physical_device();
+        let valid_bits = physical_device.queue_family_properties()
+            [ctx.queue.queue_family_index() as usize]
+            .timestamp_valid_bits
+            .ok_or_else(|| {
+                VkcvError::Query("timestamps are not supported by the compute queue".to_string())
+            })?;
+
+        let pool = QueryPool::new(
+            ctx.device.clone(),
+            QueryPoolCreateInfo {
+                query_count: 2 * stages as u32,
+                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+            },
+        )?;
+
+        Ok(Self {
+            pool,
+            period: physical_device.properties().timestamp_period,
+            valid_bits,
+            stages: Vec::with_capacity(stages),
+        })
+    }
+
+    // must be recorded before any timestamp
+    pub(crate) fn reset(&self, builder: &mut AutoCommandBufferBuilder) -> Result<(), VkcvError> {
+        unsafe {
+            builder.reset_query_pool(self.pool.clone(), 0..self.pool.query_count())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn begin(&self, builder: &mut AutoCommandBufferBuilder) -> Result<(), VkcvError> {
+        let query = 2 * self.stages.len() as u32;
+        unsafe {
+            builder.write_timestamp(self.pool.clone(), query, PipelineStage::BottomOfPipe)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn end(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder,
+        label: String,
+        image: Option<ImageInfo>,
+    ) -> Result<(), VkcvError> {
+        let query = 2 * self.stages.len() as u32 + 1;
+        unsafe {
+            builder.write_timestamp(self.pool.clone(), query, PipelineStage::BottomOfPipe)?;
+        }
+        self.stages.push((label, image));
+        Ok(())
+    }
+
+    // the durations of the last execution of the command buffer,
+    // must not be called before the command buffer was executed at least once
+    pub fn read(&self) -> Result<TimingReport, VkcvError> {
+        let count = 2 * self.stages.len() as u32;
+        let mut ticks = vec![0u64; count as usize];
+
+        if count > 0 {
+            let queries = self
+                .pool
+                .queries_range(0..count)
+                .ok_or_else(|| VkcvError::Query("query range out of bounds".to_string()))?;
+            queries.get_results(
+                &mut ticks,
+                QueryResultFlags {
+                    wait: true,
+                    ..QueryResultFlags::empty()
+                },
+            )?;
+        }
+
+        let mask = if self.valid_bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << self.valid_bits) - 1
+        };
+
+        let stages = self
+            .stages
+            .iter()
+            .zip(ticks.chunks_exact(2))
+            .map(|((label, image), ticks)| StageTiming {
+                label: label.clone(),
+                micros: (ticks[1].wrapping_sub(ticks[0]) & mask) as f64 * self.period as f64 / 1e3,
+                image: *image,
+            })
+            .collect();
+
+        Ok(TimingReport { stages })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StageTiming {
+    pub label: String,
+    pub micros: f64,
+    // the image produced by the element, resp. consumed if it produced a buffer
+    pub image: Option<ImageInfo>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TimingReport {
+    pub stages: Vec<StageTiming>,
+}
+
+impl TimingReport {
+    pub fn total_micros(&self) -> f64 {
+        self.stages.iter().map(|s| s.micros).sum()
+    }
+
+    // one line per stage, see `CSV_HEADER`
+    pub fn write_csv<W: Write>(&self, w: &mut W, frame: u64) -> std::io::Result<()> {
+        for (i, stage) in self.stages.iter().enumerate() {
+            let (width, height) = stage.image.map_or((0, 0), |info| (info.width, info.height));
+            writeln!(
+                w,
+                "{},{},\"{}\",{:.1},{},{}",
+                frame, i, stage.label, stage.micros, width, height
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            write!(f, "🠶 ({}) '{}' took {:.1} μs", i, stage.label, stage.micros)?;
+            if let Some(info) = stage.image {
+                write!(f, " [{}x{} {:?}]", info.width, info.height, info.format)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "total: {:.1} μs", self.total_micros())
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 use realsense_sys::*;
 use vulkano::format::Format::R8G8B8A8_UNORM;
 
-use crate::utils::ImageInfo;
+use crate::{error::VkcvError, utils::ImageInfo};
 
 fn check_err(err: *const rs2_error) -> Result<(), String> {
     unsafe {
@@ -493,7 +493,7 @@ impl Frame {
         unsafe { rs2_get_frame_stride_in_bytes(self.frame, ptr::null_mut()) as u32 }
     }
 
-    pub fn save(&self, image_path: &str) {
+    pub fn save(&self, image_path: &str) -> Result<(), VkcvError> {
         crate::utils::write_image(
             image_path,
             self.data_slice(),
@@ -502,7 +502,7 @@ impl Frame {
                 height: self.height(),
                 format: R8G8B8A8_UNORM,
             },
-        );
+        )
     }
 
     pub fn crop(&self, new_width: u32, new_height: u32) -> (ImageInfo, Vec<u8>) {
@@ -571,4 +571,10 @@ impl<'a> Drop for DepthFramePromise<'a> {
 
 unsafe impl<'a> Send for DepthFramePromise<'a> {}
 unsafe impl Send for ColorFrame {}
+// SAFETY: a frame is a reference counted handle owned by the wrapper, librealsense
+// allows releasing it on any thread (`rs2_release_frame` is thread safe) and its
+// data is not modified after it has been handed out by the frame queue. Only
+// read-only getters are exposed, and the frame is not `Sync`, i.e. it is accessed
+// by one thread at a time.
+unsafe impl Send for DepthFrame {}
 unsafe impl Send for Realsense {}
@@ -0,0 +1,64 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0, FORMAT) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, r8) uniform writeonly image2D resultImage;
+// rgb: mean, a: variance, 0 until the pixel has been seen once
+layout(set = 0, binding = 2, rgba32f) uniform image2D modelImage;
+
+layout(set = 0, binding = 3) uniform Params
+{
+    float learning_rate;
+    // running average: on the absolute difference of any channel
+    // gaussian: in standard deviations
+    float threshold;
+}
+params;
+
+layout(set = 0, binding = 4) uniform State
+{
+    // 1 for the first frame after a reset
+    uint reset;
+}
+state;
+
+// 0: running average
+// 1: per-pixel gaussian
+layout(constant_id = 2) const int gaussian = 0;
+
+const float INITIAL_VARIANCE = 0.01;
+const float MIN_VARIANCE = 0.0001;
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+
+    // unused channels are 0 in both the input and the model
+    vec3 value = imageLoad(inputImage, id).rgb;
+    vec4 model = imageLoad(modelImage, id);
+
+    // first frame
+    if (model.a == 0.0 || state.reset != 0u) {
+        imageStore(modelImage, id, vec4(value, INITIAL_VARIANCE));
+        imageStore(resultImage, id, vec4(0.0));
+        return;
+    }
+
+    vec3 d = value - model.rgb;
+    float d2 = dot(d, d) / float(CHANNELS);
+
+    bool foreground;
+    if (gaussian == 1) {
+        foreground = d2 > params.threshold * params.threshold * model.a;
+    } else {
+        vec3 ad = abs(d);
+        foreground = max(ad.r, max(ad.g, ad.b)) > params.threshold;
+    }
+
+    // blend the frame into the background
+    vec3 mean = mix(model.rgb, value, params.learning_rate);
+    float variance = max(mix(model.a, d2, params.learning_rate), MIN_VARIANCE);
+
+    imageStore(modelImage, id, vec4(mean, variance));
+    imageStore(resultImage, id, vec4(foreground ? 1.0 : 0.0));
+}
@@ -0,0 +1,53 @@
+#version 450
+
+// FORMAT is either r8 or rgba8 (see binary_op.rs)
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+layout(set = 0, binding = 0, FORMAT) uniform readonly image2D inputImageA;
+layout(set = 0, binding = 1, FORMAT) uniform readonly image2D inputImageB;
+layout(set = 0, binding = 2, FORMAT) uniform writeonly image2D resultImage;
+
+// 0: add, 1: subtract, 2: absolute difference, 3: multiply,
+// 4: and, 5: or, 6: not (of a), 7: min, 8: max
+layout(constant_id = 2) const int op = 0;
+
+// bitwise operations are performed on the 8bit unorm representation
+uvec4 to_bits(in vec4 v)
+{
+    return uvec4(round(clamp(v, 0.0, 1.0) * 255.0));
+}
+
+vec4 from_bits(in uvec4 v)
+{
+    return vec4(v & 0xFFu) / 255.0;
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+
+    vec4 a = imageLoad(inputImageA, id);
+    vec4 b = imageLoad(inputImageB, id);
+
+    vec4 res;
+    if (op == 0) {
+        res = a + b;
+    } else if (op == 1) {
+        res = a - b;
+    } else if (op == 2) {
+        res = abs(a - b);
+    } else if (op == 3) {
+        res = a * b;
+    } else if (op == 4) {
+        res = from_bits(to_bits(a) & to_bits(b));
+    } else if (op == 5) {
+        res = from_bits(to_bits(a) | to_bits(b));
+    } else if (op == 6) {
+        res = from_bits(~to_bits(a));
+    } else if (op == 7) {
+        res = min(a, b);
+    } else {
+        res = max(a, b);
+    }
+
+    imageStore(resultImage, id, clamp(res, 0.0, 1.0));
+}
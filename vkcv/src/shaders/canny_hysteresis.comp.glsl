@@ -0,0 +1,85 @@
+#version 450
+
+// the tile size is fixed as it determines the size of the shared memory
+#define TILE 16
+
+layout(local_size_x = TILE, local_size_y = TILE, local_size_z = 1) in;
+// 0: no edge, 0.5: weak edge, 1: strong edge
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, r8) uniform writeonly image2D resultImage;
+
+// the last iteration drops the remaining weak edges, i.e. writes a binary mask
+layout(constant_id = 0) const int final_pass = 0;
+
+// the tile including a border of 1 pixel
+shared float tile[TILE + 2][TILE + 2];
+shared bool changed;
+
+bool strong(in float v)
+{
+    return v > 0.75;
+}
+
+bool weak(in float v)
+{
+    return v > 0.25 && v < 0.75;
+}
+
+void main()
+{
+    ivec2 size = imageSize(inputImage);
+    ivec2 origin = ivec2(gl_WorkGroupID.xy) * TILE - 1;
+    ivec2 lid = ivec2(gl_LocalInvocationID.xy);
+
+    // load the tile, pixels outside of the image are no edges
+    for (int i = int(gl_LocalInvocationIndex); i < (TILE + 2) * (TILE + 2); i += TILE * TILE) {
+        ivec2 p = ivec2(i % (TILE + 2), i / (TILE + 2));
+        ivec2 q = origin + p;
+        bool inside = q.x >= 0 && q.y >= 0 && q.x < size.x && q.y < size.y;
+        tile[p.y][p.x] = inside ? imageLoad(inputImage, q).r : 0.0;
+    }
+
+    // promote weak edges connected to strong edges until nothing changes
+    // within the tile, edges crossing tiles are picked up by the next iteration
+    ivec2 t = lid + 1;
+    for (int it = 0; it < TILE * TILE; ++it) {
+        if (gl_LocalInvocationIndex == 0) {
+            changed = false;
+        }
+        barrier();
+
+        if (weak(tile[t.y][t.x])) {
+            bool connected = false;
+            for (int y = -1; y <= 1; ++y) {
+                for (int x = -1; x <= 1; ++x) {
+                    connected = connected || strong(tile[t.y + y][t.x + x]);
+                }
+            }
+
+            if (connected) {
+                tile[t.y][t.x] = 1.0;
+                changed = true;
+            }
+        }
+        barrier();
+
+        bool done = !changed;
+        barrier();
+
+        if (done) {
+            break;
+        }
+    }
+
+    ivec2 id = origin + t;
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    float res = tile[t.y][t.x];
+    if (final_pass != 0) {
+        res = strong(res) ? 1.0 : 0.0;
+    }
+
+    imageStore(resultImage, id, vec4(res));
+}
@@ -0,0 +1,60 @@
+#version 450
+
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+// gradient, magnitude and orientation, see sobel.comp.glsl
+layout(set = 0, binding = 0, rgba16f) uniform readonly image2D inputImage;
+// 0: no edge, 0.5: weak edge, 1: strong edge
+layout(set = 0, binding = 1, r8) uniform writeonly image2D resultImage;
+
+layout(set = 0, binding = 2) uniform Params
+{
+    float low;
+    float high;
+}
+params;
+
+const float PI = 3.14159265;
+
+float magnitude(in ivec2 p, in ivec2 size)
+{
+    return imageLoad(inputImage, clamp(p, ivec2(0), size - 1)).z;
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(inputImage);
+
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    vec2 g = imageLoad(inputImage, id).zw;
+
+    // quantize the gradient direction to one of the 4 neighbor axes
+    // (the sign does not matter as both neighbors are compared)
+    int sector = int(round(g.y / (PI / 4.0))) & 3;
+    ivec2 dir;
+    if (sector == 0) {
+        dir = ivec2(1, 0);
+    } else if (sector == 1) {
+        dir = ivec2(1, 1);
+    } else if (sector == 2) {
+        dir = ivec2(0, 1);
+    } else {
+        dir = ivec2(-1, 1);
+    }
+
+    // non-maximum suppression along the gradient
+    // (ties are resolved towards the "forward" neighbor to keep edges thin)
+    bool is_max = g.x > magnitude(id + dir, size) && g.x >= magnitude(id - dir, size);
+
+    float res = 0.0;
+    if (is_max && g.x >= params.high) {
+        res = 1.0;
+    } else if (is_max && g.x >= params.low) {
+        res = 0.5;
+    }
+
+    imageStore(resultImage, id, vec4(res));
+}
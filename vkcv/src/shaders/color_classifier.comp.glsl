@@ -0,0 +1,51 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D inputImage;
+// 0: background, i: class i (stored as i / 255)
+layout(set = 0, binding = 1, r8) uniform writeonly image2D resultImage;
+
+// one range per class, see color_filter.comp.glsl
+// w is unused
+layout(set = 0, binding = 2) uniform Params
+{
+    vec4 rgb_min[16];
+    vec4 rgb_max[16];
+    ivec4 invert[16];
+    int count;
+}
+params;
+
+bool inside(in vec3 rgb, in int i)
+{
+    vec3 lo = params.rgb_min[i].rgb;
+    vec3 hi = params.rgb_max[i].rgb;
+
+    // ranges with min > max wrap around
+    bvec3 in_range;
+    for (int c = 0; c < 3; ++c) {
+        bool above = rgb[c] >= lo[c];
+        bool below = rgb[c] <= hi[c];
+        in_range[c] = lo[c] <= hi[c] ? above && below : above || below;
+    }
+
+    return all(notEqual(in_range, notEqual(params.invert[i].xyz, ivec3(0))));
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+
+    vec3 rgb = imageLoad(inputImage, id).rgb;
+
+    // the first matching class wins
+    int label = 0;
+    for (int i = 0; i < params.count; ++i) {
+        if (inside(rgb, i)) {
+            label = i + 1;
+            break;
+        }
+    }
+
+    imageStore(resultImage, id, vec4(float(label) / 255.0));
+}
@@ -4,12 +4,33 @@ layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
 layout(set = 0, binding = 0, rgba8) uniform readonly image2D inputImage;
 layout(set = 0, binding = 1, r8) uniform writeonly image2D resultImage;
 
-layout(push_constant) uniform PushConstants
+// up to 8 boxes, a pixel is selected if it is inside of any of them
+// w is unused
+layout(set = 0, binding = 2) uniform Params
 {
-    vec3 rgb_max;
-    vec3 rgb_min;
+    vec4 rgb_min[8];
+    vec4 rgb_max[8];
+    // non-zero if the test of the channel is inverted
+    ivec4 invert[8];
+    int count;
+}
+params;
+
+bool inside(in vec3 rgb, in int i)
+{
+    vec3 lo = params.rgb_min[i].rgb;
+    vec3 hi = params.rgb_max[i].rgb;
+
+    // ranges with min > max wrap around, e.g. red hues
+    bvec3 in_range;
+    for (int c = 0; c < 3; ++c) {
+        bool above = rgb[c] >= lo[c];
+        bool below = rgb[c] <= hi[c];
+        in_range[c] = lo[c] <= hi[c] ? above && below : above || below;
+    }
+
+    return all(notEqual(in_range, notEqual(params.invert[i].xyz, ivec3(0))));
 }
-pc;
 
 void main()
 {
@@ -17,12 +38,13 @@ void main()
 
     vec3 rgb = imageLoad(inputImage, id).rgb;
 
-    float v;
-    if (all(greaterThanEqual(rgb, pc.rgb_min)) && all(lessThanEqual(rgb, pc.rgb_max))) {
-        v = 1.0;
-    } else {
-        v = 0.0;
+    float v = 0.0;
+    for (int i = 0; i < params.count; ++i) {
+        if (inside(rgb, i)) {
+            v = 1.0;
+            break;
+        }
     }
 
     imageStore(resultImage, id, vec4(v));
-}
\ No newline at end of file
+}
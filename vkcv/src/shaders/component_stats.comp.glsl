@@ -0,0 +1,76 @@
+#version 450
+
+// 0: clear the table, 1: accumulate
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+// 0: background, i: label i
+#ifdef UINT_LABELS
+layout(set = 0, binding = 0, r32ui) uniform readonly uimage2D inputImage;
+#else
+// stored as i / 255
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+#endif
+// one row per label, sums are 64bit (low, high):
+// area, min x, min y, max x, max y, sum x (2), sum y (2),
+// sum x^2 (2), sum y^2 (2), sum xy (2), unused
+// followed by a row holding the highest label, including the labels which
+// exceed the table
+layout(set = 0, binding = 1, r32ui) uniform coherent uimage2D table;
+
+layout(constant_id = 2) const int pass = 0;
+
+void add64(in int column, in int row, in uint v)
+{
+    uint old = imageAtomicAdd(table, ivec2(column, row), v);
+
+    // carry
+    if (old + v < old) {
+        imageAtomicAdd(table, ivec2(column + 1, row), 1u);
+    }
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+
+    if (pass == 0) {
+        ivec2 size = imageSize(table);
+        if (id.x < size.x && id.y < size.y) {
+            bool is_min = id.x == 1 || id.x == 2;
+            imageStore(table, id, uvec4(is_min ? 0xFFFFFFFFu : 0u));
+        }
+        return;
+    }
+
+    ivec2 size = imageSize(inputImage);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+#ifdef UINT_LABELS
+    uint label = imageLoad(inputImage, id).r;
+#else
+    uint label = uint(round(imageLoad(inputImage, id).r * 255.0));
+#endif
+    if (label == 0) {
+        return;
+    }
+
+    int rows = imageSize(table).y - 1;
+    imageAtomicMax(table, ivec2(0, rows), label);
+    if (label > uint(rows)) {
+        return;
+    }
+
+    int row = int(label) - 1;
+    uvec2 p = uvec2(id);
+    imageAtomicAdd(table, ivec2(0, row), 1u);
+    imageAtomicMin(table, ivec2(1, row), p.x);
+    imageAtomicMin(table, ivec2(2, row), p.y);
+    imageAtomicMax(table, ivec2(3, row), p.x);
+    imageAtomicMax(table, ivec2(4, row), p.y);
+    add64(5, row, p.x);
+    add64(7, row, p.y);
+    add64(9, row, p.x * p.x);
+    add64(11, row, p.y * p.y);
+    add64(13, row, p.x * p.y);
+}
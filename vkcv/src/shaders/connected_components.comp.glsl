@@ -0,0 +1,145 @@
+#version 450
+
+// Union-find connected component labelling, run in several passes:
+// 0: init, every foreground pixel is its own component
+// 1: merge with the (already visited) neighbors
+// 2: compress, point every pixel directly to the root of its component and
+//    accumulate the area at the root
+// 3: count, assign consecutive ids to the roots of components of at least
+//    min_area pixels
+// 4: relabel, write the id + 1 of the component of every pixel
+//
+// ref: Playne, Hawick: "A New Algorithm for Parallel Connected-Component
+// Labelling on GPUs", 2018
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+// the linear index of the parent of every pixel
+layout(set = 0, binding = 1, r32ui) uniform coherent uimage2D labels;
+// the area and then the id of the component, only valid for roots
+layout(set = 0, binding = 2, r32ui) uniform coherent uimage2D ids;
+// 1x1, the number of components
+layout(set = 0, binding = 3, r32ui) uniform coherent uimage2D counter;
+// 0: background, i: component i
+layout(set = 0, binding = 4, r32ui) uniform writeonly uimage2D resultImage;
+
+layout(constant_id = 2) const int pass = 0;
+layout(constant_id = 3) const int eight_connected = 1;
+layout(constant_id = 4) const uint min_area = 0;
+
+const uint BACKGROUND = 0xFFFFFFFFu;
+// the id of components smaller than min_area
+const uint DISCARDED = 0xFFFFFFFFu;
+
+ivec2 size;
+
+ivec2 pos(in uint i)
+{
+    return ivec2(int(i) % size.x, int(i) / size.x);
+}
+
+uint parent(in uint i)
+{
+    return imageLoad(labels, pos(i)).r;
+}
+
+uint find(in uint i)
+{
+    uint p = parent(i);
+    while (p != i) {
+        i = p;
+        p = parent(i);
+    }
+    return i;
+}
+
+// the root with the smaller index becomes the root of the union
+void unite(in uint a, in uint b)
+{
+    bool done = false;
+    while (!done) {
+        a = find(a);
+        b = find(b);
+
+        if (a < b) {
+            uint old = imageAtomicMin(labels, pos(b), a);
+            done = old == b;
+            b = old;
+        } else if (b < a) {
+            uint old = imageAtomicMin(labels, pos(a), b);
+            done = old == a;
+            a = old;
+        } else {
+            done = true;
+        }
+    }
+}
+
+void merge(in uint i, in ivec2 n)
+{
+    if (n.x >= 0 && n.y >= 0 && n.x < size.x) {
+        uint j = uint(n.y * size.x + n.x);
+        if (parent(j) != BACKGROUND) {
+            unite(i, j);
+        }
+    }
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    size = imageSize(inputImage);
+
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    uint i = uint(id.y * size.x + id.x);
+
+    if (pass == 0) {
+        bool foreground = imageLoad(inputImage, id).r > 0.5;
+        imageStore(labels, id, uvec4(foreground ? i : BACKGROUND));
+        imageStore(ids, id, uvec4(0));
+
+        if (i == 0) {
+            imageStore(counter, ivec2(0), uvec4(0));
+        }
+    } else if (pass == 1) {
+        if (parent(i) == BACKGROUND) {
+            return;
+        }
+
+        merge(i, id + ivec2(-1, 0));
+        merge(i, id + ivec2(0, -1));
+        if (eight_connected != 0) {
+            merge(i, id + ivec2(-1, -1));
+            merge(i, id + ivec2(1, -1));
+        }
+    } else if (pass == 2) {
+        if (parent(i) != BACKGROUND) {
+            uint root = find(i);
+            imageStore(labels, id, uvec4(root));
+            imageAtomicAdd(ids, pos(root), 1u);
+        }
+    } else if (pass == 3) {
+        if (parent(i) == i) {
+            // small components (i.e. noise) are removed before the ids are
+            // handed out, all others are kept
+            uint n = DISCARDED;
+            if (imageLoad(ids, id).r >= min_area) {
+                n = imageAtomicAdd(counter, ivec2(0), 1u);
+            }
+            imageStore(ids, id, uvec4(n));
+        }
+    } else {
+        uint root = parent(i);
+        uint res = 0;
+        if (root != BACKGROUND) {
+            uint n = imageLoad(ids, pos(root)).r;
+            if (n != DISCARDED) {
+                res = n + 1;
+            }
+        }
+
+        imageStore(resultImage, id, uvec4(res));
+    }
+}
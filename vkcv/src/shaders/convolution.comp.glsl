@@ -0,0 +1,77 @@
+#version 450
+
+// FORMAT is either r8 or rgba8 (see convolution.rs)
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+layout(set = 0, binding = 0, FORMAT) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, FORMAT) uniform writeonly image2D resultImage;
+
+// shared with convolution_sep.comp.glsl
+// weights are packed into vec4 (std140), up to 15x15
+layout(set = 0, binding = 2) uniform Params
+{
+    // row major
+    vec4 weights[57];
+    // factors of separable kernels
+    vec4 row[4];
+    vec4 column[4];
+    int width;
+    int height;
+    float offset;
+    float denom;
+    // 0: clamp, 1: mirror, 2: zero, 3: wrap
+    int border;
+}
+params;
+
+// maps i to [0, n), -1 if the pixel is outside and the border is zero
+int border(in int i, in int n)
+{
+    if (i >= 0 && i < n) {
+        return i;
+    }
+
+    if (params.border == 0) {
+        return clamp(i, 0, n - 1);
+    } else if (params.border == 1) {
+        // the edge pixel is not repeated
+        int period = max(2 * (n - 1), 1);
+        i = abs(i) % period;
+        return i < n ? i : period - i;
+    } else if (params.border == 2) {
+        return -1;
+    } else {
+        return ((i % n) + n) % n;
+    }
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(inputImage);
+
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    int rx = params.width / 2;
+    int ry = params.height / 2;
+
+    vec4 acc = vec4(0.0);
+    for (int y = 0; y < params.height; ++y) {
+        int sy = border(id.y + y - ry, size.y);
+        for (int x = 0; x < params.width; ++x) {
+            int sx = border(id.x + x - rx, size.x);
+            if (sx >= 0 && sy >= 0) {
+                int i = y * params.width + x;
+                acc += params.weights[i / 4][i % 4] * imageLoad(inputImage, ivec2(sx, sy));
+            }
+        }
+    }
+
+    vec4 res = clamp(acc / params.denom + params.offset, 0.0, 1.0);
+
+    // alpha is passed through
+    res.a = imageLoad(inputImage, id).a;
+
+    imageStore(resultImage, id, res);
+}
@@ -0,0 +1,89 @@
+#version 450
+
+// One pass of a separable convolution, convolving along x and storing the
+// result transposed.
+// FIRST_PASS: FORMAT (r8 or rgba8) to rgba16f using the row factor
+// otherwise: rgba16f to FORMAT using the column factor, offset and denom
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+#ifdef FIRST_PASS
+layout(set = 0, binding = 0, FORMAT) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, rgba16f) uniform writeonly image2D resultImage;
+#else
+layout(set = 0, binding = 0, rgba16f) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, FORMAT) uniform writeonly image2D resultImage;
+#endif
+
+// same as in convolution.comp.glsl
+layout(set = 0, binding = 2) uniform Params
+{
+    vec4 weights[57];
+    vec4 row[4];
+    vec4 column[4];
+    int width;
+    int height;
+    float offset;
+    float denom;
+    int border;
+}
+params;
+
+int border(in int i, in int n)
+{
+    if (i >= 0 && i < n) {
+        return i;
+    }
+
+    if (params.border == 0) {
+        return clamp(i, 0, n - 1);
+    } else if (params.border == 1) {
+        int period = max(2 * (n - 1), 1);
+        i = abs(i) % period;
+        return i < n ? i : period - i;
+    } else if (params.border == 2) {
+        return -1;
+    } else {
+        return ((i % n) + n) % n;
+    }
+}
+
+float factor(in int i)
+{
+#ifdef FIRST_PASS
+    return params.row[i / 4][i % 4];
+#else
+    return params.column[i / 4][i % 4];
+#endif
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(inputImage);
+
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+#ifdef FIRST_PASS
+    int taps = params.width;
+#else
+    int taps = params.height;
+#endif
+
+    vec4 acc = vec4(0.0);
+    for (int i = 0; i < taps; ++i) {
+        int x = border(id.x + i - taps / 2, size.x);
+        if (x >= 0) {
+            acc += factor(i) * imageLoad(inputImage, ivec2(x, id.y));
+        }
+    }
+
+#ifndef FIRST_PASS
+    acc = clamp(acc / params.denom + params.offset, 0.0, 1.0);
+#endif
+
+    // alpha is passed through
+    acc.a = imageLoad(inputImage, id).a;
+
+    imageStore(resultImage, id.yx, acc);
+}
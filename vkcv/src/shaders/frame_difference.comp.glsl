@@ -0,0 +1,47 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0, FORMAT) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, r8) uniform writeonly image2D resultImage;
+// rgb: previous frame, a: 0 until the pixel has been seen once
+layout(set = 0, binding = 2, rgba8) uniform image2D previousImage;
+
+layout(set = 0, binding = 3) uniform Params
+{
+    // binary output if > 0
+    float threshold;
+}
+params;
+
+layout(set = 0, binding = 4) uniform State
+{
+    // 1 for the first frame after a reset
+    uint reset;
+}
+state;
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+
+    // unused channels are 0 in both frames
+    vec3 value = imageLoad(inputImage, id).rgb;
+    vec4 previous = imageLoad(previousImage, id);
+
+    imageStore(previousImage, id, vec4(value, 1.0));
+
+    // no motion on the first frame
+    if (previous.a == 0.0 || state.reset != 0u) {
+        imageStore(resultImage, id, vec4(0.0));
+        return;
+    }
+
+    vec3 ad = abs(value - previous.rgb);
+    float d = max(ad.r, max(ad.g, ad.b));
+
+    if (params.threshold > 0.0) {
+        d = d > params.threshold ? 1.0 : 0.0;
+    }
+
+    imageStore(resultImage, id, vec4(d));
+}
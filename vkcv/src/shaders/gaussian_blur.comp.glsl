@@ -0,0 +1,42 @@
+#version 450
+
+// FORMAT is either r8 or rgba8 (see gaussian_blur.rs)
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+layout(set = 0, binding = 0, FORMAT) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, FORMAT) uniform writeonly image2D resultImage;
+
+// weight i is applied at a distance of i pixels, packed into vec4 (std140)
+// up to a radius of 31 pixels
+layout(set = 0, binding = 2) uniform Params
+{
+    vec4 weights[8];
+    int radius;
+}
+params;
+
+float weight(in int i)
+{
+    return params.weights[i / 4][i % 4];
+}
+
+void main()
+{
+    // one dimensional blur along x, the result is stored transposed
+    // such that running this twice blurs along x and y
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(inputImage);
+
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    vec4 acc = weight(0) * imageLoad(inputImage, id);
+    for (int i = 1; i <= params.radius; ++i) {
+        // clamp to edge
+        vec4 left = imageLoad(inputImage, ivec2(max(id.x - i, 0), id.y));
+        vec4 right = imageLoad(inputImage, ivec2(min(id.x + i, size.x - 1), id.y));
+        acc += weight(i) * (left + right);
+    }
+
+    imageStore(resultImage, id.yx, acc);
+}
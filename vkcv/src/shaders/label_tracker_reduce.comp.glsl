@@ -0,0 +1,46 @@
+#version 450
+
+// one workgroup per class
+layout(local_size_x = 256, local_size_y = 1, local_size_z = 1) in;
+// see label_tracker_tiles.comp.glsl
+layout(set = 0, binding = 0, rgba32f) uniform readonly image2D inputImage;
+// pixel i holds class i + 1 in the same format as the output of the tracker,
+// i.e. (x * area, y * area, area, area) with normalized coordinates and the
+// area as fraction of the image
+layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D resultImage;
+
+layout(constant_id = 0) const float inv_width = 1.0;
+layout(constant_id = 1) const float inv_height = 1.0;
+
+shared vec3 acc[256];
+
+void main()
+{
+    int label = int(gl_WorkGroupID.x);
+    uint lid = gl_LocalInvocationIndex;
+
+    ivec2 size = imageSize(inputImage);
+    int tiles_x = size.x;
+    int tiles_y = size.y / int(gl_NumWorkGroups.x);
+
+    vec3 sum = vec3(0.0);
+    for (int i = int(lid); i < tiles_x * tiles_y; i += 256) {
+        sum += imageLoad(inputImage, ivec2(i % tiles_x, i / tiles_x + label * tiles_y)).xyz;
+    }
+    acc[lid] = sum;
+    barrier();
+
+    for (uint s = 128; s > 0; s >>= 1) {
+        if (lid < s) {
+            acc[lid] += acc[lid + s];
+        }
+        barrier();
+    }
+
+    if (lid == 0) {
+        float norm = inv_width * inv_height;
+        vec3 t = acc[0];
+        float area = t.z * norm;
+        imageStore(resultImage, ivec2(label, 0), vec4(t.x * inv_width * norm, t.y * inv_height * norm, area, area));
+    }
+}
@@ -0,0 +1,42 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+// 0: background, i: class i (stored as i / 255)
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+// (sum x, sum y, count) per tile and class, the tiles of class i are stored
+// in rows [i * tiles_y, (i + 1) * tiles_y)
+layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D resultImage;
+
+// at most 16, see label_tracker.rs
+layout(constant_id = 0) const int classes = 1;
+
+shared uint sums[3 * 16];
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(inputImage);
+    uint lid = gl_LocalInvocationIndex;
+
+    if (lid < 3 * 16) {
+        sums[lid] = 0u;
+    }
+    barrier();
+
+    if (id.x < size.x && id.y < size.y) {
+        int label = int(round(imageLoad(inputImage, id).r * 255.0));
+        if (label >= 1 && label <= classes) {
+            int i = 3 * (label - 1);
+            atomicAdd(sums[i], uint(id.x));
+            atomicAdd(sums[i + 1], uint(id.y));
+            atomicAdd(sums[i + 2], 1u);
+        }
+    }
+    barrier();
+
+    if (lid < classes) {
+        uint i = 3 * lid;
+        ivec2 tile = ivec2(gl_WorkGroupID.xy) + ivec2(0, lid * gl_NumWorkGroups.y);
+        imageStore(resultImage, tile, vec4(sums[i], sums[i + 1], sums[i + 2], 0.0));
+    }
+}
@@ -1,8 +1,9 @@
 #version 450
 
+// FORMAT is either rgba16f or rgba32f (see tracker.rs)
 layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
-layout(set = 0, binding = 0, rgba16f) uniform readonly image2D inputImage;
-layout(set = 0, binding = 1, rgba16f) uniform writeonly image2D resultImage;
+layout(set = 0, binding = 0, FORMAT) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, FORMAT) uniform writeonly image2D resultImage;
 
 void main()
 {
@@ -0,0 +1,88 @@
+#version 450
+
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, r8) uniform writeonly image2D resultImage;
+
+// only used by separable elements, the line is along the height of the element
+// instead of its width
+layout(constant_id = 2) const int second_pass = 0;
+
+layout(set = 0, binding = 2) uniform Params
+{
+    int erode_dilate;
+}
+params;
+
+// see morphology.rs
+layout(set = 0, binding = 3) uniform StructuringElement
+{
+    // one bit per pixel, row major, up to 15x15
+    uvec4 mask[2];
+    // 0: mask, 1: cross, 2: line along x (one pass of a rectangle)
+    int shape;
+    int width;
+    int height;
+}
+element;
+
+float op(in float a, in float b)
+{
+    if (params.erode_dilate == 0) {
+        return min(a, b);
+    } else {
+        return max(a, b);
+    }
+}
+
+bool is_set(in int i)
+{
+    return (element.mask[i / 128][(i / 32) % 4] & (1u << (i % 32))) != 0u;
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(inputImage);
+
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    // pixels outside of the image are ignored
+    float res = params.erode_dilate == 0 ? 1.0 : 0.0;
+
+    int rx = element.width / 2;
+    int ry = element.height / 2;
+
+    if (element.shape == 0) {
+        for (int y = 0; y < element.height; ++y) {
+            int sy = id.y + y - ry;
+            for (int x = 0; x < element.width; ++x) {
+                int sx = id.x + x - rx;
+                if (is_set(y * element.width + x) && sx >= 0 && sy >= 0 && sx < size.x && sy < size.y) {
+                    res = op(res, imageLoad(inputImage, ivec2(sx, sy)).r);
+                }
+            }
+        }
+    } else if (element.shape == 1) {
+        for (int x = max(id.x - rx, 0); x <= min(id.x + rx, size.x - 1); ++x) {
+            res = op(res, imageLoad(inputImage, ivec2(x, id.y)).r);
+        }
+        for (int y = max(id.y - ry, 0); y <= min(id.y + ry, size.y - 1); ++y) {
+            res = op(res, imageLoad(inputImage, ivec2(id.x, y)).r);
+        }
+    } else {
+        int r = second_pass == 0 ? rx : ry;
+        for (int x = max(id.x - r, 0); x <= min(id.x + r, size.x - 1); ++x) {
+            res = op(res, imageLoad(inputImage, ivec2(x, id.y)).r);
+        }
+    }
+
+    // lines are stored transposed such that the second pass runs along x as well
+    if (element.shape == 2) {
+        imageStore(resultImage, id.yx, vec4(res));
+    } else {
+        imageStore(resultImage, id, vec4(res));
+    }
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0) uniform sampler2D inputImageSampler; // linear, black border
+layout(set = 0, binding = 1, FORMAT) uniform writeonly image2D resultImage;
+
+// the window in normalized coordinates of the input image
+layout(set = 0, binding = 2) uniform Params
+{
+    vec2 offset;
+    vec2 extent;
+}
+params;
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    vec2 size = vec2(imageSize(resultImage));
+
+    // snap to whole texels such that a 1:1 window is copied without filtering
+    vec2 tex_size = vec2(textureSize(inputImageSampler, 0));
+    vec2 offset = floor(params.offset * tex_size + 0.5) / tex_size;
+
+    vec2 uv = offset + (vec2(id) + 0.5) / size * params.extent;
+    imageStore(resultImage, id, texture(inputImageSampler, uv));
+}
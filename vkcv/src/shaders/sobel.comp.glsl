@@ -0,0 +1,37 @@
+#version 450
+
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+// signed gradient (gx, gy), magnitude and orientation in radians [-pi, pi]
+layout(set = 0, binding = 1, rgba16f) uniform writeonly image2D resultImage;
+
+float pixel(in ivec2 p, in ivec2 size)
+{
+    // clamp to edge
+    return imageLoad(inputImage, clamp(p, ivec2(0), size - 1)).r;
+}
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(inputImage);
+
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    float tl = pixel(id + ivec2(-1, -1), size);
+    float t = pixel(id + ivec2(0, -1), size);
+    float tr = pixel(id + ivec2(1, -1), size);
+    float l = pixel(id + ivec2(-1, 0), size);
+    float r = pixel(id + ivec2(1, 0), size);
+    float bl = pixel(id + ivec2(-1, 1), size);
+    float b = pixel(id + ivec2(0, 1), size);
+    float br = pixel(id + ivec2(1, 1), size);
+
+    // in [-4, 4]
+    vec2 g = vec2((tr + 2.0 * r + br) - (tl + 2.0 * l + bl),
+                  (bl + 2.0 * b + br) - (tl + 2.0 * t + tr));
+
+    imageStore(resultImage, id, vec4(g, length(g), atan(g.y, g.x)));
+}
@@ -0,0 +1,29 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+// (min x, min y, max x, max y), empty blocks are (1, 1, 0, 0)
+layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D resultImage;
+
+layout(constant_id = 3) const float inv_width = 1.0;
+layout(constant_id = 4) const float inv_height = 1.0;
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    const vec2 inv_size = vec2(inv_width, inv_height);
+
+    // bounding box of a 2x2 block
+    vec4 res = vec4(1.0, 1.0, 0.0, 0.0);
+    for (int y = 0; y < 2; ++y) {
+        for (int x = 0; x < 2; ++x) {
+            ivec2 p = id * 2 + ivec2(x, y);
+            if (imageLoad(inputImage, p).r > 0.5) {
+                vec2 c = vec2(p) * inv_size;
+                res = vec4(min(res.xy, c), max(res.zw, c));
+            }
+        }
+    }
+
+    imageStore(resultImage, id, res);
+}
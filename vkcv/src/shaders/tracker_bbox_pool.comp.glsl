@@ -0,0 +1,22 @@
+#version 450
+
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z = 1) in;
+// see tracker_bbox_mask.comp.glsl
+layout(set = 0, binding = 0, rgba32f) uniform readonly image2D inputImage;
+layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D resultImage;
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+
+    ivec2 p = id * 2;
+    vec4 a = imageLoad(inputImage, p + ivec2(0, 0));
+    vec4 b = imageLoad(inputImage, p + ivec2(0, 1));
+    vec4 c = imageLoad(inputImage, p + ivec2(1, 0));
+    vec4 d = imageLoad(inputImage, p + ivec2(1, 1));
+
+    vec2 lo = min(min(a.xy, b.xy), min(c.xy, d.xy));
+    vec2 hi = max(max(a.zw, b.zw), max(c.zw, d.zw));
+
+    imageStore(resultImage, id, vec4(lo, hi));
+}
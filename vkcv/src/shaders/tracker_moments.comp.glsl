@@ -0,0 +1,27 @@
+#version 450
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0, r8) uniform readonly image2D inputImage;
+// 1x1, the reduced coordinate mask, see tracker_cm.comp.glsl
+layout(set = 0, binding = 1, rgba16f) uniform readonly image2D centroidImage;
+layout(set = 0, binding = 2, rgba32f) uniform writeonly image2D resultImage;
+
+layout(constant_id = 3) const float inv_width = 1.0;
+layout(constant_id = 4) const float inv_height = 1.0;
+
+void main()
+{
+    ivec2 id = ivec2(gl_GlobalInvocationID.xy);
+    const vec2 inv_size = vec2(inv_width, inv_height);
+
+    // second-order central moments mask
+    // E[x^2] - E[x]^2 loses all precision for small blobs, thus the moments
+    // are taken about the already reduced centroid
+    vec4 s = imageLoad(centroidImage, ivec2(0));
+    vec2 c = s.z > 0.0 ? s.xy / s.z : vec2(0.0);
+
+    float r = imageLoad(inputImage, id).r;
+    vec2 p = vec2(id) * inv_size - c;
+
+    imageStore(resultImage, id, vec4(r * p * p, r * p.x * p.y, r));
+}
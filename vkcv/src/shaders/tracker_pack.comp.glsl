@@ -0,0 +1,16 @@
+#version 450
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+// 1x1 each
+layout(set = 0, binding = 0, rgba16f) uniform readonly image2D centroidImage;
+layout(set = 0, binding = 1, rgba32f) uniform readonly image2D momentsImage;
+layout(set = 0, binding = 2, rgba32f) uniform readonly image2D bboxImage;
+// 3x1, see tracker::extent
+layout(set = 0, binding = 3, rgba32f) uniform writeonly image2D resultImage;
+
+void main()
+{
+    imageStore(resultImage, ivec2(0, 0), imageLoad(centroidImage, ivec2(0)));
+    imageStore(resultImage, ivec2(1, 0), imageLoad(momentsImage, ivec2(0)));
+    imageStore(resultImage, ivec2(2, 0), imageLoad(bboxImage, ivec2(0)));
+}
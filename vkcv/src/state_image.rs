@@ -0,0 +1,159 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use vulkano::{
+    buffer::{BufferUsage, CpuAccessibleBuffer},
+    command_buffer::{
+        AutoCommandBufferBuilder, ClearColorImageInfo, CommandBufferUsage,
+        PrimaryCommandBufferAbstract,
+    },
+    format::ClearColorValue,
+    image::{view::ImageView, StorageImage},
+    sync::GpuFuture,
+};
+
+use crate::{
+    error::VkcvError,
+    processing_elements,
+    utils::{self, ImageInfo},
+    vk_init::VkContext,
+};
+
+// An image keeping its content between submissions, e.g. the previous frame.
+//
+// Unlike the images handed out by the image pool, a state image is never
+// shared with other stages. It is cleared once when created, i.e. a shader
+// can recognize the first submission, and a shader can be told to start
+// over after a `reset` (see `bind_reset`).
+//
+// An element keeps a single state for all pipelines it is built into (see
+// `SharedState`), thus these must not execute concurrently. E.g. the
+// `FramePipeline` submits its frames in order such that the state sees
+// every frame.
+pub struct StateImage {
+    image: Arc<StorageImage>,
+    // set by `reset`, taken by the next submission
+    reset: Arc<AtomicBool>,
+}
+
+impl StateImage {
+    pub fn new(
+        ctx: &VkContext,
+        info: &ImageInfo,
+        clear: ClearColorValue,
+    ) -> Result<Self, VkcvError> {
+        let image = utils::allocate_storage_image(ctx, info)?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &ctx.memory.command_buffer_allocator,
+            ctx.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        builder.clear_color_image(ClearColorImageInfo {
+            clear_value: clear,
+            ..ClearColorImageInfo::image(image.clone())
+        })?;
+        builder
+            .build()?
+            .execute(ctx.queue.clone())?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        Ok(Self {
+            image,
+            reset: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    // cleared to 0
+    pub fn zeroed(ctx: &VkContext, info: &ImageInfo) -> Result<Self, VkcvError> {
+        Self::new(ctx, info, ClearColorValue::Float([0.0; 4]))
+    }
+
+    pub fn image(&self) -> Arc<StorageImage> {
+        self.image.clone()
+    }
+
+    pub fn view(&self) -> Result<Arc<ImageView<StorageImage>>, VkcvError> {
+        Ok(ImageView::new_default(self.image.clone())?)
+    }
+
+    // the next submission of any pipeline using the state starts over
+    pub fn reset(&self) {
+        self.reset.store(true, Ordering::SeqCst);
+    }
+
+    // a uniform block `{ uint reset; }`, which is 1 for the first submission
+    // after `reset` and 0 otherwise
+    pub(crate) fn bind_reset(
+        &self,
+        ctx: &VkContext,
+        builder: &mut processing_elements::AutoCommandBufferBuilder,
+    ) -> Result<Arc<CpuAccessibleBuffer<u32>>, VkcvError> {
+        let buffer = CpuAccessibleBuffer::from_data(
+            &ctx.memory.allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..Default::default()
+            },
+            true,
+            0u32,
+        )?;
+
+        let reset = self.reset.clone();
+        let bound = buffer.clone();
+        builder.on_submit(move || {
+            *bound.write()? = reset.swap(false, Ordering::SeqCst) as u32;
+            Ok(())
+        });
+
+        Ok(buffer)
+    }
+}
+
+// The state of an element, created by the first build and shared by all
+// pipelines the element is built into.
+#[derive(Default)]
+pub struct SharedState {
+    image: Mutex<Option<Arc<StateImage>>>,
+}
+
+impl SharedState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // all pipelines are required to have the same input
+    pub fn get_or_create(
+        &self,
+        ctx: &VkContext,
+        info: &ImageInfo,
+        clear: ClearColorValue,
+    ) -> Result<Arc<StateImage>, VkcvError> {
+        let mut image = self.image.lock().unwrap();
+
+        if let Some(state) = &*image {
+            if ImageInfo::from(&state.image) != *info {
+                return Err(VkcvError::InvalidParameters(format!(
+                    "the state has been created for {:?}, got {:?}",
+                    ImageInfo::from(&state.image),
+                    info
+                )));
+            }
+            return Ok(state.clone());
+        }
+
+        let state = Arc::new(StateImage::new(ctx, info, clear)?);
+        *image = Some(state.clone());
+        Ok(state)
+    }
+
+    // see `StateImage::reset`, nothing to do before the first build
+    pub fn reset(&self) {
+        if let Some(state) = &*self.image.lock().unwrap() {
+            state.reset();
+        }
+    }
+}
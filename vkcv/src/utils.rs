@@ -2,17 +2,24 @@ use std::borrow::Cow;
 use std::sync::Arc;
 use std::{fs::File, io::BufWriter, path::Path};
 
-use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::device::Device;
 pub use vulkano::format::Format;
 use vulkano::image::{ImageAccess, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
-use vulkano::sync::{self, GpuFuture};
+use vulkano::pipeline::ComputePipeline;
+use vulkano::shader::{ShaderCreationError, ShaderModule, SpecializationConstants};
 
 use crate::endpoints::image_download::ImageDownload;
+use crate::error::VkcvError;
+use crate::image_pool::MemoryReport;
+use crate::processing_elements::graph::PipelineGraph;
 use crate::processing_elements::output::Output;
-use crate::processing_elements::{IoFragment, PipeInput, PipeOutput, ProcessingElement};
+use crate::processing_elements::{
+    AutoCommandBufferBuilder, IoFragment, PipeInput, PipeOutput, ProcessingElement, Recording,
+};
+use crate::profiler::{Profiler, TimingReport};
 use crate::vk_init::VkContext;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ImageInfo {
     pub width: u32,
     pub height: u32,
@@ -55,13 +62,13 @@ impl From<&Arc<StorageImage>> for ImageInfo {
     }
 }
 
-pub fn load_image(image_path: &str) -> (ImageInfo, Vec<u8>) {
+pub fn load_image(image_path: &str) -> Result<(ImageInfo, Vec<u8>), VkcvError> {
     let p = format!("{}/media/{}", env!("CARGO_MANIFEST_DIR"), image_path);
     let path = Path::new(&p);
-    let decoder = png::Decoder::new(File::open(path).unwrap());
-    let mut reader = decoder.read_info().unwrap();
+    let decoder = png::Decoder::new(File::open(path)?);
+    let mut reader = decoder.read_info()?;
     let mut img_data = vec![0; reader.output_buffer_size()];
-    let oi = reader.next_frame(&mut img_data).unwrap();
+    let oi = reader.next_frame(&mut img_data)?;
 
     println!(
         "Loaded image '{}' ({}x{}) format {:?} {:?} bits",
@@ -72,19 +79,21 @@ pub fn load_image(image_path: &str) -> (ImageInfo, Vec<u8>) {
 
     // currently we only support RGBA images since RGB images cannot be
     // optimally represented by the raspberry
-    match ct {
-        png::ColorType::Rgba => (),
-        _ => panic!("RGBA format required!"),
+    if ct != png::ColorType::Rgba {
+        return Err(VkcvError::Io(format!(
+            "'{}': RGBA format required, got {:?}",
+            image_path, ct
+        )));
     }
 
-    (
+    Ok((
         ImageInfo {
             width: oi.width,
             height: oi.height,
             format: Format::R8G8B8A8_UNORM,
         },
         img_data,
-    )
+    ))
 }
 
 pub fn convert_sfloat_to_unorm(width: u32, height: u32, data: &[u8]) -> Vec<u8> {
@@ -129,10 +138,10 @@ pub fn convert_sfloat_to_unorm(width: u32, height: u32, data: &[u8]) -> Vec<u8>
     conv_data
 }
 
-pub fn write_image(image_path: &str, data: &[u8], img_info: &ImageInfo) {
+pub fn write_image(image_path: &str, data: &[u8], img_info: &ImageInfo) -> Result<(), VkcvError> {
     let p = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), image_path);
     let path = Path::new(&p);
-    let file = File::create(path).unwrap();
+    let file = File::create(path)?;
     let ref mut w = BufWriter::new(file);
     let mut encoder = png::Encoder::new(w, img_info.width, img_info.height);
     let mut buffer = Cow::from(data);
@@ -151,16 +160,36 @@ pub fn write_image(image_path: &str, data: &[u8], img_info: &ImageInfo) {
         }
         format => {
             println!("Cannot save format {:?}", format.type_color());
-            return;
+            return Ok(());
         }
     }
 
     encoder.set_depth(png::BitDepth::Eight);
-    let mut writer = encoder.write_header().unwrap();
-    writer.write_image_data(&buffer).unwrap();
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&buffer)?;
+    Ok(())
+}
+
+// takes the image from the image pool of the pipeline being recorded
+pub fn create_storage_image(
+    ctx: &VkContext,
+    builder: &mut AutoCommandBufferBuilder,
+    img_info: &ImageInfo,
+) -> Result<Arc<StorageImage>, VkcvError> {
+    builder.image_pool().acquire(ctx, img_info)
+}
+
+// hands an image back to the image pool, i.e. the image must not be used by
+// any command recorded afterwards
+pub fn release_storage_image(builder: &mut AutoCommandBufferBuilder, image: Arc<StorageImage>) {
+    builder.image_pool().release(image);
 }
 
-pub fn create_storage_image(ctx: &VkContext, img_info: &ImageInfo) -> Arc<StorageImage> {
+// allocates a new image, bypassing the image pool
+pub fn allocate_storage_image(
+    ctx: &VkContext,
+    img_info: &ImageInfo,
+) -> Result<Arc<StorageImage>, VkcvError> {
     let usage = ImageUsage {
         storage: true,
         sampled: true,
@@ -170,7 +199,7 @@ pub fn create_storage_image(ctx: &VkContext, img_info: &ImageInfo) -> Arc<Storag
     };
     let flags = ImageCreateFlags::empty();
 
-    StorageImage::with_usage(
+    Ok(StorageImage::with_usage(
         &ctx.memory.allocator,
         ImageDimensions::Dim2d {
             width: img_info.width,
@@ -181,8 +210,21 @@ pub fn create_storage_image(ctx: &VkContext, img_info: &ImageInfo) -> Arc<Storag
         usage,
         flags,
         Some(ctx.queue.queue_family_index()),
-    )
-    .unwrap()
+    )?)
+}
+
+// takes the generated `cs::load`, pipelines are taken from the context's
+// cache if the shader was used with the same specialization constants before
+pub fn compute_pipeline<S, L>(
+    ctx: &VkContext,
+    load: L,
+    spec: &S,
+) -> Result<Arc<ComputePipeline>, VkcvError>
+where
+    S: SpecializationConstants + 'static,
+    L: FnOnce(Arc<Device>) -> Result<Arc<ShaderModule>, ShaderCreationError>,
+{
+    ctx.pipeline_cache.get_or_create(load, spec)
 }
 
 pub fn workgroups(dimensions: &[u32; 2], local_size: &[u32; 2]) -> [u32; 3] {
@@ -198,64 +240,78 @@ pub fn cv_pipeline_sequential<I, O>(
     input: &I,
     elements: &[&dyn ProcessingElement],
     output: &O,
-) -> (Arc<PrimaryAutoCommandBuffer>, IoFragment, IoFragment)
+) -> Result<(Recording, IoFragment, IoFragment), VkcvError>
 where
     I: PipeInput + ProcessingElement,
     O: PipeOutput + ProcessingElement,
 {
-    let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
-        &ctx.memory.command_buffer_allocator,
-        ctx.queue.queue_family_index(),
-        vulkano::command_buffer::CommandBufferUsage::MultipleSubmit,
-    )
-    .unwrap();
-
-    let dummy = IoFragment::none();
-    let input_io = input.build(ctx, &mut builder, &dummy);
-
-    let mut io_fragments = vec![input_io.clone()];
+    let names: Vec<_> = (0..elements.len())
+        .map(|i| format!("stage_{}", i))
+        .collect();
 
-    for pe in elements {
-        io_fragments.push(pe.build(ctx, &mut builder, io_fragments.last().as_ref().unwrap()));
-    }
+    // only the input and the output are handed out, thus the images of the
+    // stages in between can be reused
+    let mut graph = PipelineGraph::new();
+    graph.reuse_images(true);
+    let input_node = graph.add_source("input", input);
+    let chain: Vec<_> = names
+        .iter()
+        .map(|n| n.as_str())
+        .zip(elements.iter().copied())
+        .collect();
+    let last = graph.add_chain(input_node, &chain);
+    let output_node = graph.add("output", output);
+    graph.connect(last, output_node);
 
-    let output_io = output.build(ctx, &mut builder, io_fragments.last().as_ref().unwrap());
+    let pipeline = graph.build(ctx)?;
 
-    let command_buffer = Arc::new(builder.build().unwrap());
-    (command_buffer, input_io, output_io)
+    Ok((
+        pipeline.recording.clone(),
+        pipeline.io(input_node).clone(),
+        pipeline.io(output_node).clone(),
+    ))
 }
 
 pub fn cv_pipeline_sequential_with_taps<I, O>(
     ctx: &VkContext,
     input: &I,
     elements: &[&dyn ProcessingElement],
-) -> (Arc<PrimaryAutoCommandBuffer>, IoFragment, Vec<IoFragment>)
+) -> Result<(Recording, IoFragment, Vec<IoFragment>), VkcvError>
 where
     I: PipeInput + ProcessingElement,
     O: PipeOutput + ProcessingElement + Default,
 {
-    let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
-        &ctx.memory.command_buffer_allocator,
-        ctx.queue.queue_family_index(),
-        vulkano::command_buffer::CommandBufferUsage::MultipleSubmit,
-    )
-    .unwrap();
+    let taps: Vec<_> = elements.iter().map(|_| O::default()).collect();
 
-    let dummy = IoFragment::none();
-    let input_io = input.build(ctx, &mut builder, &dummy);
+    // the taps copy the images of the stages into their own buffers
+    let mut graph = PipelineGraph::new();
+    graph.reuse_images(true);
+    let input_node = graph.add_source("input", input);
 
-    let mut io_fragments = vec![input_io.clone()];
-    let mut outputs = vec![];
+    let mut last = input_node;
+    let mut tap_nodes = vec![];
+    for (i, pe) in elements.iter().enumerate() {
+        let node = graph.add(&format!("stage_{}", i), *pe);
+        graph.connect(last, node);
 
-    for pe in elements {
-        io_fragments.push(pe.build(ctx, &mut builder, io_fragments.last().as_ref().unwrap()));
-        let output = O::default();
-        let io_out = output.build(ctx, &mut builder, io_fragments.last().as_ref().unwrap());
-        outputs.push(io_out);
+        let tap = graph.add(&format!("tap_{}", i), &taps[i]);
+        graph.connect(node, tap);
+        tap_nodes.push(tap);
+
+        last = node;
     }
 
-    let command_buffer = Arc::new(builder.build().unwrap());
-    (command_buffer, input_io, outputs)
+    let pipeline = graph.build(ctx)?;
+    let outputs = tap_nodes
+        .iter()
+        .map(|tap| pipeline.io(*tap).clone())
+        .collect();
+
+    Ok((
+        pipeline.recording.clone(),
+        pipeline.io(input_node).clone(),
+        outputs,
+    ))
 }
 
 pub fn cv_pipeline_sequential_debug<I, O>(
@@ -263,123 +319,89 @@ pub fn cv_pipeline_sequential_debug<I, O>(
     input: &I,
     elements: &[&dyn ProcessingElement],
     output: &O,
-) -> DebugPipeline
+) -> Result<DebugPipeline, VkcvError>
 where
     I: PipeInput + ProcessingElement,
     O: PipeOutput + ProcessingElement,
 {
-    let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
-        &ctx.memory.command_buffer_allocator,
-        ctx.queue.queue_family_index(),
-        vulkano::command_buffer::CommandBufferUsage::MultipleSubmit,
-    )
-    .unwrap();
-
-    let dummy = IoFragment::none();
-    let input_io = input.build(ctx, &mut builder, &dummy);
-
-    let mut io_fragments = vec![input_io.clone()];
+    let generic_output = Output::new();
 
-    for pe in elements {
-        io_fragments.push(pe.build(ctx, &mut builder, io_fragments.last().as_ref().unwrap()));
+    // the debug outputs copy the images of the stages into their own buffers
+    let mut graph = PipelineGraph::new();
+    graph.reuse_images(true);
+    graph.profile(true);
+    let input_node = graph.add_source("input", input);
+    let mut stage_nodes = vec![input_node];
+    for (i, pe) in elements.iter().enumerate() {
+        let node = graph.add(&format!("stage_{}", i), *pe);
+        graph.connect(*stage_nodes.last().unwrap(), node);
+        stage_nodes.push(node);
     }
-
-    let output_io = output.build(ctx, &mut builder, io_fragments.last().as_ref().unwrap());
+    let output_node = graph.add("output", output);
+    graph.connect(*stage_nodes.last().unwrap(), output_node);
 
     // create generic outputs for each io element in the pipeline
-    let generic_output = Output::new();
-    let generic_output_ios: Vec<_> = io_fragments
+    let generic_output_nodes: Vec<_> = stage_nodes
         .iter()
-        .map(|io| generic_output.build(ctx, &mut builder, io))
+        .enumerate()
+        .map(|(i, node)| {
+            let tap = graph.add(&format!("debug_output_{}", i), &generic_output);
+            graph.connect(*node, tap);
+            tap
+        })
         .collect();
 
-    // finalize the command buffer
-    let command_buffer = Arc::new(builder.build().unwrap());
-
-    // create individual command buffers
-    let mut indiv_io_elements = vec![input_io.clone()];
-    let mut stage_labels = vec![];
-    let mut individual_cbs = vec![];
-    for pe in elements {
-        let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
-            &ctx.memory.command_buffer_allocator,
-            ctx.queue.queue_family_index(),
-            vulkano::command_buffer::CommandBufferUsage::MultipleSubmit,
-        )
-        .unwrap();
-
-        let io = pe.build(
-            ctx,
-            &mut builder,
-            indiv_io_elements.last().as_ref().unwrap(),
-        );
-        stage_labels.push(io.label.to_string());
-
-        indiv_io_elements.push(io);
-        individual_cbs.push(Arc::new(builder.build().unwrap()));
-    }
-
-    DebugPipeline {
-        cb: command_buffer,
+    let pipeline = graph.build(ctx)?;
+    let input_io = pipeline.io(input_node).clone();
+    let output_io = pipeline.io(output_node).clone();
+    let generic_output_ios: Vec<_> = generic_output_nodes
+        .iter()
+        .map(|node| pipeline.io(*node).clone())
+        .collect();
+    Ok(DebugPipeline {
+        recording: pipeline.recording,
         input: input_io,
         output: output_io,
         debug_outputs: generic_output_ios,
-        individual_cbs,
-        stage_labels,
-    }
+        memory: pipeline.memory,
+        profiler: pipeline.profiler.unwrap(),
+    })
 }
 
 pub struct DebugPipeline {
-    pub cb: Arc<PrimaryAutoCommandBuffer>,
+    pub recording: Recording,
     pub input: IoFragment,
     pub output: IoFragment,
     pub debug_outputs: Vec<IoFragment>,
-    pub individual_cbs: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    pub memory: MemoryReport,
 
-    stage_labels: Vec<String>,
+    profiler: Profiler,
 }
 
 impl DebugPipeline {
-    pub fn dispatch(&self, ctx: &VkContext) {
-        let future = sync::now(ctx.device.clone())
-            .then_execute(ctx.queue.clone(), self.cb.clone())
-            .unwrap()
-            .then_signal_fence_and_flush()
-            .unwrap();
-
-        future.wait(None).unwrap();
+    pub fn dispatch(&self, ctx: &VkContext) -> Result<(), VkcvError> {
+        self.recording.submit(ctx)?.wait(None)?;
+        Ok(())
     }
 
-    pub fn time(&self, ctx: &VkContext) {
-        for (i, cb) in self.individual_cbs.iter().enumerate() {
-            let future = sync::now(ctx.device.clone())
-                .then_execute(ctx.queue.clone(), cb.clone())
-                .unwrap()
-                .then_signal_fence_and_flush()
-                .unwrap();
-
-            let started = std::time::Instant::now();
-            future.wait(None).unwrap();
-            let dt = std::time::Instant::now() - started;
-
-            println!(
-                "🠶 ({}) '{}' took {} μs",
-                i,
-                self.stage_labels[i],
-                dt.as_micros()
-            );
-        }
+    // runs the pipeline once and prints the GPU time spent in each stage
+    pub fn time(&self, ctx: &VkContext) -> Result<TimingReport, VkcvError> {
+        self.dispatch(ctx)?;
+        let report = self.profiler.read()?;
+        println!("{}", report);
+        Ok(report)
     }
 
-    pub fn save_all(&self, ctx: &VkContext, dir: &str, prefix: &str) {
-        self.dispatch(ctx);
-        std::fs::create_dir_all(dir).unwrap();
+    pub fn save_all(&self, ctx: &VkContext, dir: &str, prefix: &str) -> Result<(), VkcvError> {
+        self.dispatch(ctx)?;
+        std::fs::create_dir_all(dir)?;
         for (i, io) in self.debug_outputs.iter().enumerate() {
-            let mut download = ImageDownload::from_io(io.clone()).unwrap();
+            let mut download = ImageDownload::from_io(io.clone())?;
             download
-                .transfer()
-                .save_output_buffer(&format!("{}/{}{}.png", dir, prefix, i))
+                .transfer()?
+                .save_output_buffer(&format!("{}/{}{}.png", dir, prefix, i))?;
         }
+        Ok(())
     }
 }
 
@@ -427,7 +449,20 @@ pub fn image_to_rgba8(info: &ImageInfo, data: &[u8]) -> (ImageInfo, Vec<u8>) {
 
             (new_info, out)
         }
-        Format::R16G16B16A16_SFLOAT => (new_info, out), // TODO
+        Format::R16G16B16A16_SFLOAT => {
+            // e.g. the output of `Sobel`, the signed gradients are mapped from
+            // [-4, 4] and the magnitude from [0, 4] to [0, 255]
+            for (i, cnk) in data.chunks_exact(8).enumerate() {
+                let x = half::f16::from_le_bytes([cnk[0], cnk[1]]).to_f32();
+                let y = half::f16::from_le_bytes([cnk[2], cnk[3]]).to_f32();
+                let m = half::f16::from_le_bytes([cnk[4], cnk[5]]).to_f32();
+                out[i * 4] = ((x / 8.0 + 0.5).clamp(0.0, 1.0) * 255.0) as u8;
+                out[i * 4 + 1] = ((y / 8.0 + 0.5).clamp(0.0, 1.0) * 255.0) as u8;
+                out[i * 4 + 2] = ((m / 4.0).clamp(0.0, 1.0) * 255.0) as u8;
+            }
+
+            (new_info, out)
+        }
         _ => panic!("unsuppored format {:?}", info.format),
     }
 }
@@ -1,4 +1,3 @@
-use std::error::Error;
 use std::sync::Arc;
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
@@ -10,6 +9,8 @@ use vulkano::memory::allocator::{
 };
 use vulkano::{Version, VulkanLibrary};
 
+use crate::{error::VkcvError, pipeline_cache::PipelineCache};
+
 // type MemoryAllocator
 pub struct Memory {
     pub allocator: GenericMemoryAllocator<Arc<FreeListAllocator>>,
@@ -21,15 +22,16 @@ pub struct VkContext {
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
     pub memory: Memory,
+    pub pipeline_cache: PipelineCache,
 }
 
-pub fn init() -> Result<VkContext, Box<dyn Error>> {
+pub fn init() -> Result<VkContext, VkcvError> {
     // Note RPI4 claims VK1.1 'compliance'
     let ci = InstanceCreateInfo {
         max_api_version: Some(Version::V1_1),
         ..Default::default()
     };
-    let instance = Instance::new(VulkanLibrary::new().unwrap(), ci)?;
+    let instance = Instance::new(VulkanLibrary::new()?, ci)?;
 
     // extensions
     let device_extensions = DeviceExtensions {
@@ -40,8 +42,7 @@ pub fn init() -> Result<VkContext, Box<dyn Error>> {
 
     // queue devices
     let (physical_device, queue_family_index) = instance
-        .enumerate_physical_devices()
-        .unwrap()
+        .enumerate_physical_devices()?
         .filter(|p| p.supported_extensions().contains(&device_extensions))
         .filter_map(|p| {
             // The Vulkan specs guarantee that a compliant implementation must provide at least one queue
@@ -59,7 +60,7 @@ pub fn init() -> Result<VkContext, Box<dyn Error>> {
             PhysicalDeviceType::Other => 4,
             _ => 5,
         })
-        .unwrap();
+        .ok_or_else(|| VkcvError::Init("no suitable compute device found".to_string()))?;
 
     println!(
         ">> Selected Device: '{}' (type: '{:?}')",
@@ -80,7 +81,11 @@ pub fn init() -> Result<VkContext, Box<dyn Error>> {
         },
     )?;
 
-    let queue = queues.next().unwrap();
+    let pipeline_cache = PipelineCache::new(device.clone())?;
+
+    let queue = queues
+        .next()
+        .ok_or_else(|| VkcvError::Init("no compute queue available".to_string()))?;
     let allocator = StandardMemoryAllocator::new_default(device.clone());
     let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
         device.clone(),
@@ -98,5 +103,6 @@ pub fn init() -> Result<VkContext, Box<dyn Error>> {
         device,
         queue,
         memory,
+        pipeline_cache,
     })
 }